@@ -0,0 +1,895 @@
+use crate::decoder;
+use crate::formatter;
+use crate::hwregs::HW_REGISTERS;
+use crate::instruction::{AddressingModeKind, OPCODES, OpcodeDef};
+use crate::memory::SliceMemory;
+use crate::spc::{Config, Spc};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+#[derive(Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// A run of assembled bytes starting at `addr`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+}
+
+/// Assembles source written in the syntax produced by the disassembler.
+///
+/// Besides instructions, the source may contain `label:` definitions,
+/// `name = expr` (or `name equ expr`) constants, and the `org`, `db` and
+/// `dw` directives. Listing columns (`0800: 8f 6c f2  `, and the
+/// `--cycles` column) are skipped, and the first listing address becomes
+/// the origin if there is no `org`. The I/O register names from
+/// `--hw-regs` are predefined.
+pub fn assemble(source: &str) -> Result<Vec<Segment>, AsmError> {
+    let mut symbols: HashMap<String, i64> = HW_REGISTERS
+        .iter()
+        .enumerate()
+        .map(|(i, reg)| (reg.name.to_string(), 0xF0 + i as i64))
+        .collect();
+    let mut statements = Vec::new();
+    let mut pc: u16 = 0;
+    let mut origin_set = false;
+    let mut pending_labels: Vec<(usize, String)> = Vec::new();
+
+    // Pass 1: parse every line, pick an encoding and assign addresses.
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_no = index + 1;
+        let err = |message: String| AsmError {
+            line: line_no,
+            message,
+        };
+
+        let (listing_addr, mut text) = strip_listing_prefix(strip_comment(raw_line));
+        if let Some(addr) = listing_addr
+            && !origin_set
+        {
+            pc = addr;
+            origin_set = true;
+            statements.push(Statement {
+                line: line_no,
+                pc,
+                kind: StatementKind::Org,
+            });
+        }
+
+        // Labels name the next statement, so a label line printed ahead of
+        // a listing line picks up that line's address.
+        while let Some((name, rest)) = split_label(text) {
+            pending_labels.push((line_no, name.to_string()));
+            text = rest;
+        }
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        if let Some((name, value)) = constant_definition(text) {
+            let tokens = tokenize(value).map_err(err)?;
+            let expr = parse_single_expr(&tokens).map_err(err)?;
+            let value = expr
+                .eval(&symbols)
+                .ok_or_else(|| err(format!("'{}' must be defined before use", name)))?;
+            define(&mut symbols, line_no, name, value)?;
+            continue;
+        }
+
+        let (keyword, rest) = match text.split_once(char::is_whitespace) {
+            Some((keyword, rest)) => (keyword, rest.trim()),
+            None => (text, ""),
+        };
+        let tokens = tokenize(rest).map_err(err)?;
+
+        let kind = match keyword.to_ascii_lowercase().as_str() {
+            "org" => {
+                let expr = parse_single_expr(&tokens).map_err(err)?;
+                let value = expr
+                    .eval(&symbols)
+                    .ok_or_else(|| err("org address must be known".to_string()))?;
+                pc = check_range(value, 0, 0xFFFF, "org address").map_err(err)? as u16;
+                origin_set = true;
+                StatementKind::Org
+            }
+            "db" => StatementKind::Bytes(parse_list(&tokens).map_err(err)?),
+            "dw" => StatementKind::Words(parse_list(&tokens).map_err(err)?),
+            mnemonic => {
+                let parts = parse_operands(&tokens).map_err(err)?;
+                let template = select_template(mnemonic, &parts, &symbols)
+                    .ok_or_else(|| err(format!("no encoding for '{}'", text)))?;
+                StatementKind::Instruction { template, parts }
+            }
+        };
+
+        for (line, name) in pending_labels.drain(..) {
+            define(&mut symbols, line, &name, pc as i64)?;
+        }
+        let statement = Statement {
+            line: line_no,
+            pc,
+            kind,
+        };
+        pc = pc.wrapping_add(statement.size() as u16);
+        statements.push(statement);
+    }
+
+    for (line, name) in pending_labels {
+        define(&mut symbols, line, &name, pc as i64)?;
+    }
+
+    // Pass 2: evaluate operands now that every symbol is known.
+    let mut segments: Vec<Segment> = Vec::new();
+    for statement in &statements {
+        let err = |message: String| AsmError {
+            line: statement.line,
+            message,
+        };
+        let bytes = match &statement.kind {
+            StatementKind::Org => {
+                segments.push(Segment {
+                    addr: statement.pc,
+                    bytes: Vec::new(),
+                });
+                continue;
+            }
+            StatementKind::Bytes(items) => {
+                let mut bytes = Vec::new();
+                for item in items {
+                    match item {
+                        ListItem::Str(s) => bytes.extend_from_slice(s.as_bytes()),
+                        ListItem::Expr(expr) => {
+                            let value = expr.resolve(&symbols).map_err(err)?;
+                            bytes.push(check_range(value, -0x80, 0xFF, "byte").map_err(err)? as u8);
+                        }
+                    }
+                }
+                bytes
+            }
+            StatementKind::Words(items) => {
+                let mut bytes = Vec::new();
+                for item in items {
+                    let ListItem::Expr(expr) = item else {
+                        return Err(err("dw does not take strings".to_string()));
+                    };
+                    let value = expr.resolve(&symbols).map_err(err)?;
+                    let word = check_range(value, -0x8000, 0xFFFF, "word").map_err(err)? as u16;
+                    bytes.extend_from_slice(&word.to_le_bytes());
+                }
+                bytes
+            }
+            StatementKind::Instruction { template, parts } => {
+                encode(template, parts, statement.pc, &symbols).map_err(err)?
+            }
+        };
+        if segments.is_empty() {
+            segments.push(Segment {
+                addr: statement.pc,
+                bytes: Vec::new(),
+            });
+        }
+        segments.last_mut().unwrap().bytes.extend(bytes);
+    }
+
+    segments.retain(|segment| !segment.bytes.is_empty());
+    Ok(segments)
+}
+
+struct Statement {
+    line: usize,
+    pc: u16,
+    kind: StatementKind,
+}
+
+enum StatementKind {
+    Org,
+    Bytes(Vec<ListItem>),
+    Words(Vec<ListItem>),
+    Instruction {
+        template: &'static Template,
+        parts: Vec<Part>,
+    },
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match &self.kind {
+            StatementKind::Org => 0,
+            StatementKind::Bytes(items) => items
+                .iter()
+                .map(|item| match item {
+                    ListItem::Str(s) => s.len(),
+                    ListItem::Expr(_) => 1,
+                })
+                .sum(),
+            StatementKind::Words(items) => items.len() * 2,
+            StatementKind::Instruction { template, .. } => template.def.len as usize,
+        }
+    }
+}
+
+enum ListItem {
+    Str(String),
+    Expr(Expr),
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Splits off the `xxxx: ` address and hex byte columns of a listing line.
+fn strip_listing_prefix(line: &str) -> (Option<u16>, &str) {
+    let bytes = line.as_bytes();
+    let is_prefix = bytes.len() >= 5
+        && bytes[..4].iter().all(u8::is_ascii_hexdigit)
+        && bytes[4] == b':'
+        && bytes.get(5) == Some(&b' ');
+    if !is_prefix {
+        return (None, line);
+    }
+    let addr = u16::from_str_radix(&line[..4], 16).ok();
+    let rest = line.get(6..).unwrap_or("");
+
+    // The hex column is ten characters wide: up to three bytes and padding.
+    let column = rest.get(..10).unwrap_or(rest);
+    let mut fields = column.split(' ').filter(|f| !f.is_empty());
+    let is_hex_column = column.len() == 10 || column.len() == rest.len();
+    let all_hex = fields
+        .clone()
+        .all(|f| f.len() == 2 && f.bytes().all(|c| c.is_ascii_hexdigit()));
+    if is_hex_column && all_hex && fields.next().is_some() && column.ends_with(' ') {
+        return (addr, strip_cycles_column(&rest[column.len()..]));
+    }
+    (addr, strip_cycles_column(rest))
+}
+
+/// Skips the `--cycles` column: `2/4 [12]`.
+fn strip_cycles_column(text: &str) -> &str {
+    let trimmed = text.trim_start();
+    let Some((cycles, rest)) = trimmed.split_once(' ') else {
+        return text;
+    };
+    let rest = rest.trim_start();
+    let Some(total_end) = rest.find(']') else {
+        return text;
+    };
+    let is_cycles = !cycles.is_empty() && cycles.bytes().all(|c| c.is_ascii_digit() || c == b'/');
+    let total = &rest[..total_end];
+    let is_total = total
+        .strip_prefix('[')
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit()));
+    if is_cycles && is_total {
+        &rest[total_end + 1..]
+    } else {
+        text
+    }
+}
+
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_start();
+    let end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    let name = &text[..end];
+    let starts_ok = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    if starts_ok && text[end..].starts_with(':') {
+        Some((name, &text[end + 1..]))
+    } else {
+        None
+    }
+}
+
+/// Recognizes `name = expr` and `name equ expr`.
+fn constant_definition(text: &str) -> Option<(&str, &str)> {
+    let (name, value) = match text.split_once('=') {
+        Some((name, value)) => (name.trim(), value),
+        None => {
+            let (name, rest) = text.split_once(char::is_whitespace)?;
+            let (keyword, value) = rest.trim_start().split_once(char::is_whitespace)?;
+            if !keyword.eq_ignore_ascii_case("equ") {
+                return None;
+            }
+            (name, value)
+        }
+    };
+    let is_ident = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    is_ident.then_some((name, value))
+}
+
+fn define(
+    symbols: &mut HashMap<String, i64>,
+    line: usize,
+    name: &str,
+    value: i64,
+) -> Result<(), AsmError> {
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(AsmError {
+            line,
+            message: format!("duplicate symbol '{}'", name),
+        });
+    }
+    Ok(())
+}
+
+fn check_range(value: i64, min: i64, max: i64, what: &str) -> Result<i64, String> {
+    if (min..=max).contains(&value) {
+        Ok(value & 0xFFFF)
+    } else {
+        Err(format!("{} out of range: {}", what, value))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Punct(char),
+    Ident(String),
+    /// `short` is set for numbers written as at most two hex digits (or a
+    /// decimal below 256), which select direct-page encodings.
+    Num {
+        value: i64,
+        short: bool,
+    },
+    Str(String),
+}
+
+const REGISTERS: [&str; 7] = ["a", "x", "y", "ya", "sp", "c", "psw"];
+
+fn is_register(name: &str) -> bool {
+    REGISTERS.contains(&name.to_ascii_lowercase().as_str())
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let take_while = |i: &mut usize, pred: fn(char) -> bool| {
+        let start = *i;
+        while *i < chars.len() && pred(chars[*i]) {
+            *i += 1;
+        }
+        chars[start..*i].iter().collect::<String>()
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '$' || c == '%' {
+            i += 1;
+            let (radix, digits) = if c == '$' {
+                (16, take_while(&mut i, |c| c.is_ascii_hexdigit()))
+            } else {
+                (2, take_while(&mut i, |c| c == '0' || c == '1'))
+            };
+            let value = i64::from_str_radix(&digits, radix)
+                .map_err(|_| format!("invalid number '{}{}'", c, digits))?;
+            let short = if radix == 16 {
+                digits.len() <= 2
+            } else {
+                digits.len() <= 8
+            };
+            tokens.push(Token::Num { value, short });
+        } else if c.is_ascii_digit() {
+            let digits = take_while(&mut i, |c| c.is_ascii_alphanumeric());
+            let value = match digits.strip_prefix("0x") {
+                Some(hex) => i64::from_str_radix(hex, 16),
+                None => digits.parse(),
+            }
+            .map_err(|_| format!("invalid number '{}'", digits))?;
+            tokens.push(Token::Num {
+                value,
+                short: value <= 0xFF,
+            });
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let name = take_while(&mut i, |c| c.is_ascii_alphanumeric() || c == '_');
+            tokens.push(Token::Ident(name));
+        } else if c == '"' {
+            i += 1;
+            let s = take_while(&mut i, |c| c != '"');
+            if i == chars.len() {
+                return Err("unterminated string".to_string());
+            }
+            i += 1;
+            tokens.push(Token::Str(s));
+        } else {
+            tokens.push(Token::Punct(c));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Num { value: i64, short: bool },
+    Symbol(String),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, symbols: &HashMap<String, i64>) -> Option<i64> {
+        match self {
+            Expr::Num { value, .. } => Some(*value),
+            Expr::Symbol(name) => symbols.get(name).copied(),
+            Expr::Neg(e) => Some(-e.eval(symbols)?),
+            Expr::Add(a, b) => Some(a.eval(symbols)? + b.eval(symbols)?),
+            Expr::Sub(a, b) => Some(a.eval(symbols)? - b.eval(symbols)?),
+        }
+    }
+
+    fn resolve(&self, symbols: &HashMap<String, i64>) -> Result<i64, String> {
+        self.eval(symbols).ok_or_else(|| {
+            let mut missing = self;
+            while let Expr::Neg(e) | Expr::Add(e, _) | Expr::Sub(e, _) = missing {
+                missing = e;
+            }
+            match missing {
+                Expr::Symbol(name) if !symbols.contains_key(name) => {
+                    format!("undefined symbol '{}'", name)
+                }
+                _ => "undefined symbol in expression".to_string(),
+            }
+        })
+    }
+
+    /// True if the value is known to fit a direct-page operand.
+    fn is_short(&self, symbols: &HashMap<String, i64>) -> bool {
+        match self {
+            Expr::Num { short, .. } => *short,
+            _ => self.eval(symbols).is_some_and(|v| (0..=0xFF).contains(&v)),
+        }
+    }
+}
+
+/// Parses one expression starting at `tokens[*pos]`. A `+` followed by a
+/// register name ends the expression so `$04+x` keeps its index.
+fn parse_expr(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    let mut expr = parse_atom(tokens, pos)?;
+    while let Some(Token::Punct(op @ ('+' | '-'))) = tokens.get(*pos) {
+        if let Some(Token::Ident(name)) = tokens.get(*pos + 1)
+            && is_register(name)
+        {
+            break;
+        }
+        if !matches!(
+            tokens.get(*pos + 1),
+            Some(Token::Num { .. } | Token::Ident(_) | Token::Punct('-'))
+        ) {
+            break;
+        }
+        let op = *op;
+        *pos += 1;
+        let rhs = parse_atom(tokens, pos)?;
+        expr = if op == '+' {
+            Expr::Add(Box::new(expr), Box::new(rhs))
+        } else {
+            Expr::Sub(Box::new(expr), Box::new(rhs))
+        };
+    }
+    Ok(expr)
+}
+
+fn parse_atom(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    let token = tokens.get(*pos).ok_or("missing operand")?;
+    *pos += 1;
+    match token {
+        Token::Num { value, short } => Ok(Expr::Num {
+            value: *value,
+            short: *short,
+        }),
+        Token::Ident(name) if !is_register(name) => Ok(Expr::Symbol(name.clone())),
+        Token::Punct('-') => Ok(Expr::Neg(Box::new(parse_atom(tokens, pos)?))),
+        _ => Err(format!("unexpected {:?}", token)),
+    }
+}
+
+fn parse_single_expr(tokens: &[Token]) -> Result<Expr, String> {
+    let mut pos = 0;
+    let expr = parse_expr(tokens, &mut pos)?;
+    if pos != tokens.len() {
+        return Err("unexpected text after expression".to_string());
+    }
+    Ok(expr)
+}
+
+fn parse_list(tokens: &[Token]) -> Result<Vec<ListItem>, String> {
+    let mut items = Vec::new();
+    let mut pos = 0;
+    while pos < tokens.len() {
+        if let Token::Str(s) = &tokens[pos] {
+            items.push(ListItem::Str(s.clone()));
+            pos += 1;
+        } else {
+            items.push(ListItem::Expr(parse_expr(tokens, &mut pos)?));
+        }
+        match tokens.get(pos) {
+            None => break,
+            Some(Token::Punct(',')) => pos += 1,
+            Some(token) => return Err(format!("expected ',' but found {:?}", token)),
+        }
+    }
+    if items.is_empty() {
+        return Err("empty data list".to_string());
+    }
+    Ok(items)
+}
+
+/// An operand broken into fixed syntax (registers and punctuation) and
+/// expressions.
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(Token),
+    Expr(Expr),
+}
+
+fn parse_operands(tokens: &[Token]) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut pos = 0;
+    while pos < tokens.len() {
+        match &tokens[pos] {
+            Token::Ident(name) if is_register(name) => {
+                parts.push(Part::Literal(Token::Ident(name.to_ascii_lowercase())));
+                pos += 1;
+            }
+            Token::Num { .. } | Token::Ident(_) => {
+                parts.push(Part::Expr(parse_expr(tokens, &mut pos)?))
+            }
+            Token::Punct('-') if !matches!(parts.last(), Some(Part::Expr(_))) => {
+                parts.push(Part::Expr(parse_expr(tokens, &mut pos)?))
+            }
+            Token::Str(_) => return Err("unexpected string operand".to_string()),
+            token => {
+                parts.push(Part::Literal(token.clone()));
+                pos += 1;
+            }
+        }
+    }
+    Ok(parts)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Slot {
+    Imm,
+    Dp,
+    DpSrc,
+    Abs,
+    MemAddr,
+    Bit,
+    Rel,
+    PCall,
+}
+
+#[derive(Debug)]
+enum Piece {
+    Literal(Token),
+    Slot(Slot),
+    Const(i64),
+}
+
+#[derive(Debug)]
+struct Template {
+    def: &'static OpcodeDef,
+    pieces: Vec<Piece>,
+}
+
+impl Template {
+    fn matches(&self, parts: &[Part], symbols: &HashMap<String, i64>) -> bool {
+        self.pieces.len() == parts.len()
+            && self
+                .pieces
+                .iter()
+                .zip(parts)
+                .all(|(piece, part)| match (piece, part) {
+                    (Piece::Literal(a), Part::Literal(b)) => a == b,
+                    (Piece::Slot(_), Part::Expr(_)) => true,
+                    (Piece::Const(c), Part::Expr(e)) => e.eval(symbols) == Some(*c),
+                    _ => false,
+                })
+    }
+
+    fn slot_exprs<'a>(&'a self, parts: &'a [Part]) -> impl Iterator<Item = (Slot, &'a Expr)> {
+        self.pieces
+            .iter()
+            .zip(parts)
+            .filter_map(|(piece, part)| match (piece, part) {
+                (Piece::Slot(slot), Part::Expr(expr)) => Some((*slot, expr)),
+                _ => None,
+            })
+    }
+
+    fn has_dp_slot(&self) -> bool {
+        self.pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Slot(Slot::Dp | Slot::DpSrc)))
+    }
+}
+
+// Operand values used to render each opcode once; every value is distinct so
+// the rendered text shows where each operand field goes.
+const TEMPLATE_PC: u16 = 0x4000;
+const SENTINEL_IMM: u8 = 0xB7;
+const SENTINEL_DP: u8 = 0xD7;
+const SENTINEL_DP_SRC: u8 = 0xE9;
+const SENTINEL_ABS: u16 = 0xABCD;
+const SENTINEL_MEM_ADDR: u16 = 0x1ABC;
+const SENTINEL_BIT: u16 = 5;
+const SENTINEL_REL: u8 = 0x30;
+
+/// Builds the operand syntax of every opcode by running it through the
+/// formatter with sentinel operands, so the assembler accepts exactly what
+/// the disassembler prints.
+fn templates() -> &'static HashMap<&'static str, Vec<Template>> {
+    static TEMPLATES: OnceLock<HashMap<&'static str, Vec<Template>>> = OnceLock::new();
+    TEMPLATES.get_or_init(|| {
+        let config = Config {
+            show_addr: false,
+            show_hex: false,
+            ..Config::default()
+        };
+        let spc = Spc::new(config, TEMPLATE_PC, TEMPLATE_PC);
+        let mut templates: HashMap<&'static str, Vec<Template>> = HashMap::new();
+
+        for def in OPCODES.iter() {
+            if def.mode == AddressingModeKind::Invalid {
+                continue;
+            }
+            let bytes = sentinel_bytes(def);
+            let mem = SliceMemory::new(&bytes, TEMPLATE_PC);
+            let Ok(instr) = decoder::decode_one(&mem, TEMPLATE_PC) else {
+                continue;
+            };
+            let rel_target = instr.branch_target();
+            let text = formatter::format_code(&instr, &spc);
+            let operand = text.split_once(' ').map_or("", |(_, rest)| rest);
+            let tokens = tokenize(operand).expect("formatter output tokenizes");
+
+            let pieces = tokens
+                .into_iter()
+                .map(|token| match token {
+                    Token::Num { value, .. } => match slot_for(def.mode, value, rel_target) {
+                        Some(slot) => Piece::Slot(slot),
+                        None => Piece::Const(value),
+                    },
+                    Token::Ident(name) => Piece::Literal(Token::Ident(name.to_ascii_lowercase())),
+                    token => Piece::Literal(token),
+                })
+                .collect();
+            templates
+                .entry(def.mnemonic)
+                .or_default()
+                .push(Template { def, pieces });
+        }
+        templates
+    })
+}
+
+fn sentinel_bytes(def: &OpcodeDef) -> Vec<u8> {
+    use AddressingModeKind::*;
+    let [abs_lo, abs_hi] = SENTINEL_ABS.to_le_bytes();
+    let [bit_lo, bit_hi] = (SENTINEL_MEM_ADDR | SENTINEL_BIT << 13).to_le_bytes();
+    let operands = match def.mode {
+        ImmediateByte => vec![SENTINEL_IMM],
+        DirectPage | DirectPageX | DirectPageY | IndirectX | IndirectY | DirectPageBit | PCall => {
+            vec![SENTINEL_DP]
+        }
+        Relative | ImpliedRelative => vec![SENTINEL_REL],
+        Absolute | AbsoluteX | AbsoluteY => vec![abs_lo, abs_hi],
+        MemoryBit | MemoryBitNegated => vec![bit_lo, bit_hi],
+        DirectPageBitRelative | DirectPageRelative | DirectPageXRelative => {
+            vec![SENTINEL_DP, SENTINEL_REL]
+        }
+        DirectPageToDirectPage => vec![SENTINEL_DP_SRC, SENTINEL_DP],
+        DirectPageImmediate => vec![SENTINEL_IMM, SENTINEL_DP],
+        _ => vec![],
+    };
+    let mut bytes = vec![def.opcode];
+    bytes.extend(operands);
+    bytes
+}
+
+fn slot_for(mode: AddressingModeKind, value: i64, rel_target: Option<u16>) -> Option<Slot> {
+    use AddressingModeKind::*;
+    if rel_target.is_some_and(|target| target as i64 == value) {
+        return Some(Slot::Rel);
+    }
+    match (mode, value) {
+        (PCall, v) if v == SENTINEL_DP as i64 => Some(Slot::PCall),
+        (_, v) if v == SENTINEL_DP as i64 => Some(Slot::Dp),
+        (_, v) if v == SENTINEL_DP_SRC as i64 => Some(Slot::DpSrc),
+        (_, v) if v == SENTINEL_IMM as i64 => Some(Slot::Imm),
+        (_, v) if v == SENTINEL_ABS as i64 => Some(Slot::Abs),
+        (_, v) if v == SENTINEL_MEM_ADDR as i64 => Some(Slot::MemAddr),
+        (MemoryBit | MemoryBitNegated, v) if v == SENTINEL_BIT as i64 => Some(Slot::Bit),
+        _ => None,
+    }
+}
+
+/// Picks the encoding for an instruction. When both a direct-page and an
+/// absolute form match, the direct-page form is used only if its operands
+/// are known to fit, which keeps sizes stable between the two passes.
+fn select_template(
+    mnemonic: &str,
+    parts: &[Part],
+    symbols: &HashMap<String, i64>,
+) -> Option<&'static Template> {
+    let candidates: Vec<&Template> = templates()
+        .get(mnemonic)?
+        .iter()
+        .filter(|template| template.matches(parts, symbols))
+        .collect();
+
+    let fits_dp = |template: &&&Template| {
+        template.has_dp_slot()
+            && template
+                .slot_exprs(parts)
+                .filter(|(slot, _)| matches!(slot, Slot::Dp | Slot::DpSrc))
+                .all(|(_, expr)| expr.is_short(symbols))
+    };
+    candidates
+        .iter()
+        .find(fits_dp)
+        .or_else(|| candidates.iter().find(|t| !t.has_dp_slot()))
+        .or_else(|| candidates.first())
+        .copied()
+}
+
+fn encode(
+    template: &Template,
+    parts: &[Part],
+    pc: u16,
+    symbols: &HashMap<String, i64>,
+) -> Result<Vec<u8>, String> {
+    use AddressingModeKind::*;
+    let def = template.def;
+    let next_pc = pc.wrapping_add(def.len as u16) as i64;
+
+    let mut values: HashMap<Slot, i64> = HashMap::new();
+    for (slot, expr) in template.slot_exprs(parts) {
+        let value = expr.resolve(symbols)?;
+        let value = match slot {
+            Slot::Imm => check_range(value, -0x80, 0xFF, "immediate")? & 0xFF,
+            Slot::Dp | Slot::DpSrc => check_range(value, 0, 0xFF, "direct page address")?,
+            Slot::Abs => check_range(value, 0, 0xFFFF, "address")?,
+            Slot::MemAddr => check_range(value, 0, 0x1FFF, "bit address")?,
+            Slot::Bit => check_range(value, 0, 7, "bit number")?,
+            Slot::PCall => match value {
+                0..=0xFF | 0xFF00..=0xFFFF => value & 0xFF,
+                _ => return Err(format!("pcall target out of range: {}", value)),
+            },
+            Slot::Rel => {
+                let offset = (value - next_pc) as i16 as i64;
+                check_range(offset, -0x80, 0x7F, "branch distance")? & 0xFF
+            }
+        };
+        values.insert(slot, value);
+    }
+    let get = |slot: Slot| values.get(&slot).copied().unwrap_or(0) as u8;
+    let abs = values.get(&Slot::Abs).copied().unwrap_or(0) as u16;
+    let bit_word = values.get(&Slot::MemAddr).copied().unwrap_or(0) as u16
+        | (values.get(&Slot::Bit).copied().unwrap_or(0) as u16) << 13;
+
+    let operands = match def.mode {
+        ImmediateByte => vec![get(Slot::Imm)],
+        DirectPage | DirectPageX | DirectPageY | IndirectX | IndirectY | DirectPageBit => {
+            vec![get(Slot::Dp)]
+        }
+        PCall => vec![get(Slot::PCall)],
+        Relative | ImpliedRelative => vec![get(Slot::Rel)],
+        Absolute | AbsoluteX | AbsoluteY => abs.to_le_bytes().to_vec(),
+        MemoryBit | MemoryBitNegated => bit_word.to_le_bytes().to_vec(),
+        DirectPageBitRelative | DirectPageRelative | DirectPageXRelative => {
+            vec![get(Slot::Dp), get(Slot::Rel)]
+        }
+        DirectPageToDirectPage => vec![get(Slot::DpSrc), get(Slot::Dp)],
+        DirectPageImmediate => vec![get(Slot::Imm), get(Slot::Dp)],
+        _ => vec![],
+    };
+    let mut bytes = vec![def.opcode];
+    bytes.extend(operands);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::listing;
+
+    fn listing_config() -> Config {
+        Config {
+            show_addr: false,
+            show_hex: false,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn round_trips_every_opcode() {
+        let spc = Spc::new(listing_config(), 0x0800, 0x0800);
+        for opcode in 0..=0xFFu8 {
+            let bytes = [opcode, 0x12, 0x34];
+            let mem = SliceMemory::new(&bytes, 0x0800);
+            let instr = decoder::decode_one(&mem, 0x0800).unwrap();
+            let text = formatter::format_instruction(&instr, &spc);
+
+            let source = format!("org $0800\n{}\n", text);
+            let segments = assemble(&source)
+                .unwrap_or_else(|e| panic!("opcode {:02x} '{}': {}", opcode, text, e));
+            let len = instr.definition.len as usize;
+            assert_eq!(segments.len(), 1, "opcode {:02x} '{}'", opcode, text);
+            assert_eq!(segments[0].addr, 0x0800);
+            assert_eq!(
+                segments[0].bytes,
+                bytes[..len],
+                "opcode {:02x} '{}'",
+                opcode,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_a_listing_with_labels() {
+        let code = [
+            0x8F, 0x6C, 0xF2, // mov $f2,#$6c
+            0xE8, 0x20, // mov a,#$20
+            0xC4, 0xF3, // mov $f3,a
+            0x3F, 0x10, 0x08, // call $0810
+            0xD0, 0xF4, // bne $0800
+            0x2F, 0xFE, // bra $080c
+            0x00, 0x00, // nop, nop
+            0xAB, 0x30, // inc $30
+            0x6F, // ret
+        ];
+        let config = Config {
+            gen_labels: true,
+            hw_regs: true,
+            ..Config::default()
+        };
+        let mut spc = Spc::new(config, 0x0800, 0x0800 + code.len() as u16);
+        spc.load_rom(&code, 0x0800);
+
+        let mut text = Vec::new();
+        listing::write_listing(&mut text, &mut spc, None).unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        let segments = assemble(&text).unwrap();
+        assert_eq!(
+            segments,
+            vec![Segment {
+                addr: 0x0800,
+                bytes: code.to_vec()
+            }]
+        );
+    }
+}
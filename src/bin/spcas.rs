@@ -0,0 +1,102 @@
+use clap::Parser;
+use spcdas_rs::{assembler, parse_addr, spcfile};
+use std::fs;
+use std::io;
+use std::process;
+
+/// Assembles SPC700 source written in spcdas syntax.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(required = true)]
+    input_file: String,
+
+    #[arg(required = true)]
+    output_file: String,
+
+    /// Write the assembled bytes over a copy of this raw or .spc image
+    #[arg(long, value_name = "IMAGE")]
+    patch: Option<String>,
+
+    /// Load address of a raw --patch image
+    #[arg(long, default_value = "0000", value_name = "ADDR")]
+    load: String,
+}
+
+fn main() -> io::Result<()> {
+    let args = Args::parse();
+
+    let load_addr = parse_addr(&args.load).unwrap_or_else(|e| {
+        eprintln!(
+            "Error: Invalid hex value for --load argument '{}': {}",
+            args.load, e
+        );
+        process::exit(1);
+    });
+
+    let source = fs::read_to_string(&args.input_file).unwrap_or_else(|e| {
+        eprintln!(
+            "Error: Could not read input file '{}': {}",
+            args.input_file, e
+        );
+        process::exit(1);
+    });
+
+    let segments = assembler::assemble(&source).unwrap_or_else(|e| {
+        eprintln!("Error: {}: {}", args.input_file, e);
+        process::exit(1);
+    });
+
+    let output = match &args.patch {
+        Some(path) => {
+            let mut image = fs::read(path).unwrap_or_else(|e| {
+                eprintln!("Error: Could not read patch image '{}': {}", path, e);
+                process::exit(1);
+            });
+            let base = if spcfile::is_spc(&image) {
+                spcfile::RAM_OFFSET as i64
+            } else {
+                -(load_addr as i64)
+            };
+            for segment in &segments {
+                for (i, &byte) in segment.bytes.iter().enumerate() {
+                    let addr = segment.addr.wrapping_add(i as u16);
+                    let offset = base + addr as i64;
+                    match image.get_mut(offset as usize) {
+                        Some(slot) if offset >= 0 => *slot = byte,
+                        _ => {
+                            eprintln!(
+                                "Error: Address ${:04x} is outside the patch image '{}'",
+                                addr, path
+                            );
+                            process::exit(1);
+                        }
+                    }
+                }
+            }
+            image
+        }
+        None => flatten(&segments),
+    };
+
+    fs::write(&args.output_file, output)
+}
+
+/// Lays the segments out as one binary from the lowest to the highest
+/// assembled address, filling gaps with $ff.
+fn flatten(segments: &[assembler::Segment]) -> Vec<u8> {
+    let Some(start) = segments.iter().map(|s| s.addr as usize).min() else {
+        return Vec::new();
+    };
+    let end = segments
+        .iter()
+        .map(|s| s.addr as usize + s.bytes.len())
+        .max()
+        .unwrap_or(start);
+    let mut image = vec![0xFF; end - start];
+    for segment in segments {
+        let offset = segment.addr as usize - start;
+        image[offset..offset + segment.bytes.len()].copy_from_slice(&segment.bytes);
+    }
+    image
+}
@@ -1,35 +1,228 @@
 use clap::Parser;
-use std::num::ParseIntError;
+use spcdas_rs::project::Project;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    #[arg(required = true)]
-    pub input_file: String,
+    #[arg(required_unless_present = "project")]
+    pub input_file: Option<String>,
 
-    #[arg(required = true)]
-    pub output_file: String,
+    #[arg(required_unless_present = "project")]
+    pub output_file: Option<String>,
 
-    #[arg(long, default_value = "0000", value_name = "ADDR")]
-    pub load: String,
+    #[arg(long, value_name = "FILE")]
+    pub project: Option<String>,
+
+    #[arg(long, value_name = "ADDR")]
+    pub load: Option<String>,
 
     #[arg(long, value_name = "ADDR")]
     pub pc: Option<String>,
 
-    #[arg(long, default_value = "eof", value_name = "ADDR|eof")]
-    pub stop: String,
+    #[arg(long, value_name = "ADDR|eof")]
+    pub stop: Option<String>,
+
+    #[arg(long, value_name = "START:END")]
+    pub range: Vec<String>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
+    pub raw: Option<bool>,
+
+    #[arg(long, value_name = "on|off|auto")]
+    pub ipl: Option<String>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
+    pub traverse: Option<bool>,
+
+    #[arg(long, value_name = "ADDR")]
+    pub entry: Vec<String>,
+
+    #[arg(long, value_name = "START:END[:KIND]")]
+    pub data: Vec<String>,
+
+    #[arg(long, value_name = "ADDR:COUNT")]
+    pub jump_table: Vec<String>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
+    pub labels: Option<bool>,
+
+    #[arg(long, value_name = "FILE")]
+    pub symbols: Vec<String>,
 
-    #[arg(long)]
-    pub no_addr: bool,
+    #[arg(long = "comments", value_name = "FILE")]
+    pub comments: Vec<String>,
 
-    #[arg(long)]
-    pub no_hex: bool,
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
+    pub hw_regs: Option<bool>,
 
-    #[arg(long)]
-    pub no_rel_resolve: bool,
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
+    pub dsp_regs: Option<bool>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
+    pub cycles: Option<bool>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
+    pub xrefs: Option<bool>,
+
+    #[arg(long, value_name = "FILE")]
+    pub xref_report: Option<String>,
+
+    #[arg(long, value_name = "NAME")]
+    pub syntax: Option<String>,
+
+    #[arg(long, value_name = "text|json|jsonl|html")]
+    pub format: Option<String>,
+
+    #[arg(long, value_name = "N")]
+    pub trace: Option<u64>,
+
+    #[arg(long, requires = "trace")]
+    pub trace_cycles: bool,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
+    pub no_addr: Option<bool>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
+    pub no_hex: Option<bool>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
+    pub no_rel_resolve: Option<bool>,
 }
 
-pub fn parse_hex(s: &str) -> Result<u16, ParseIntError> {
-    let s = s.trim_start_matches("0x");
-    u16::from_str_radix(s, 16)
+/// Fills in what the command line leaves unset from a project file.
+/// Command-line values win, including flags switched off with
+/// `--flag=false`, and lists are combined. The project's data regions,
+/// comments and extra inputs are applied separately.
+pub fn apply_project(args: &mut Args, project: &Project) {
+    let hex = |addr: u16| format!("{:04x}", addr);
+    if args.input_file.is_none()
+        && let Some(input) = project.inputs.first()
+    {
+        args.input_file = Some(input.path.to_string_lossy().into_owned());
+        args.load = args.load.take().or(input.load.map(hex));
+        args.raw.get_or_insert(input.raw);
+    }
+    if args.output_file.is_none() {
+        args.output_file = project
+            .output
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned());
+    }
+    if args.xref_report.is_none() {
+        args.xref_report = project
+            .xref_report
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned());
+    }
+    args.pc = args.pc.take().or(project.pc.map(hex));
+    args.stop = args.stop.take().or(project.stop.map(hex));
+    args.ipl = args
+        .ipl
+        .take()
+        .or(project.options.ipl.map(|mode| mode.to_string()));
+    args.syntax = args
+        .syntax
+        .take()
+        .or(project.options.syntax.map(|dialect| dialect.to_string()));
+    args.format = args
+        .format
+        .take()
+        .or(project.options.format.map(|format| format.to_string()));
+
+    let mut ranges: Vec<String> = project
+        .ranges
+        .iter()
+        .map(|range| format!("{}:{}", hex(range.start), hex(range.end)))
+        .collect();
+    ranges.append(&mut args.range);
+    args.range = ranges;
+    let mut entries: Vec<String> = project.entries.iter().map(|&a| hex(a)).collect();
+    entries.append(&mut args.entry);
+    args.entry = entries;
+    // Later symbol files override earlier ones, so the command line's go last.
+    let mut symbols: Vec<String> = project
+        .symbols
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    symbols.append(&mut args.symbols);
+    args.symbols = symbols;
+    let mut comments: Vec<String> = project
+        .comment_files
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    comments.append(&mut args.comments);
+    args.comments = comments;
+
+    let options = &project.options;
+    args.traverse.get_or_insert(options.traverse);
+    args.labels.get_or_insert(options.labels);
+    args.hw_regs.get_or_insert(options.hw_regs);
+    args.dsp_regs.get_or_insert(options.dsp_regs);
+    args.cycles.get_or_insert(options.cycles);
+    args.xrefs.get_or_insert(options.xrefs);
+    args.no_addr.get_or_insert(options.no_addr);
+    args.no_hex.get_or_insert(options.no_hex);
+    args.no_rel_resolve.get_or_insert(options.no_rel_resolve);
 }
@@ -0,0 +1,191 @@
+use crate::parse_addr;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Notes attached to addresses: a comment on the label line (from symbol
+/// files), an end-of-line comment on the line at the address and block
+/// comment lines above it.
+#[derive(Debug, Default)]
+pub struct Comments {
+    label: BTreeMap<u16, String>,
+    inline: BTreeMap<u16, String>,
+    block: BTreeMap<u16, Vec<String>>,
+}
+
+impl Comments {
+    /// Comment shown on the label line at `addr`, or on a line of its own
+    /// when there is no label.
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.label.get(&addr).map(String::as_str)
+    }
+
+    /// End-of-line comment for the line at `addr`.
+    pub fn inline(&self, addr: u16) -> Option<&str> {
+        self.inline.get(&addr).map(String::as_str)
+    }
+
+    /// Comment lines written above the line at `addr`.
+    pub fn block(&self, addr: u16) -> &[String] {
+        self.block.get(&addr).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn contains(&self, addr: u16) -> bool {
+        self.label.contains_key(&addr)
+            || self.inline.contains_key(&addr)
+            || self.block.contains_key(&addr)
+    }
+
+    /// Sets the label line comment at `addr`, replacing any earlier one.
+    pub fn set_label(&mut self, addr: u16, comment: String) {
+        self.label.insert(addr, comment);
+    }
+
+    /// Sets the end-of-line comment at `addr`, replacing any earlier one.
+    pub fn set_inline(&mut self, addr: u16, comment: String) {
+        self.inline.insert(addr, comment);
+    }
+
+    /// Adds a line to the block comment above `addr`.
+    pub fn push_block(&mut self, addr: u16, line: String) {
+        self.block.entry(addr).or_default().push(line);
+    }
+
+    /// Adds the comments from `other`; its end-of-line comments replace
+    /// existing ones and its block lines follow existing ones.
+    pub fn merge(&mut self, other: Comments) {
+        self.label.extend(other.label);
+        self.inline.extend(other.inline);
+        for (addr, lines) in other.block {
+            self.block.entry(addr).or_default().extend(lines);
+        }
+    }
+
+    /// Moves the comments at `from` to `to`, after any already there. Used
+    /// for addresses inside an instruction, which have no line of their
+    /// own.
+    pub fn move_to(&mut self, from: u16, to: u16) {
+        fn join(map: &mut BTreeMap<u16, String>, from: u16, to: u16) {
+            if let Some(comment) = map.remove(&from) {
+                map.entry(to)
+                    .and_modify(|existing| *existing = format!("{}; {}", existing, comment))
+                    .or_insert(comment);
+            }
+        }
+        join(&mut self.label, from, to);
+        join(&mut self.inline, from, to);
+        if let Some(lines) = self.block.remove(&from) {
+            self.block.entry(to).or_default().extend(lines);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CommentError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CommentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parses a comments file. `ADDR text` sets the end-of-line comment at
+/// `ADDR`; `ADDR: text` adds a line to the block comment above it, so
+/// repeated `ADDR:` lines build up a paragraph:
+///
+/// ```text
+/// 0800: Sequence command dispatcher
+/// 0800:
+/// 0800: Commands $e0-$ff index the table at $0a40.
+/// 0804 out-of-range commands end the track
+/// ```
+///
+/// Blank lines and lines starting with `;` or `#` are ignored.
+pub fn parse(text: &str) -> Result<Comments, CommentError> {
+    let mut comments = Comments::default();
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        let (addr_field, rest) = match line.split_once(char::is_whitespace) {
+            Some((field, rest)) => (field, rest.trim()),
+            None => (line, ""),
+        };
+        let (addr_field, is_block) = match addr_field.strip_suffix(':') {
+            Some(field) => (field, true),
+            None => (addr_field, false),
+        };
+        let Ok(addr) = parse_addr(addr_field) else {
+            return Err(CommentError {
+                line: index + 1,
+                message: format!("invalid address in '{}'", line),
+            });
+        };
+        if is_block {
+            comments.push_block(addr, rest.to_string());
+        } else if rest.is_empty() {
+            return Err(CommentError {
+                line: index + 1,
+                message: format!("missing comment in '{}'", line),
+            });
+        } else {
+            comments.set_inline(addr, rest.to_string());
+        }
+    }
+    Ok(comments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_inline_and_block_comments() {
+        let comments = parse(
+            "; notes\n\
+             # more notes\n\
+             \n\
+             0800: Sequence command dispatcher\n\
+             $0800:\n\
+             0x0800: Commands index the table.\n\
+             0804 out-of-range   commands\n",
+        )
+        .unwrap();
+        assert_eq!(
+            comments.block(0x0800),
+            [
+                "Sequence command dispatcher",
+                "",
+                "Commands index the table."
+            ]
+        );
+        assert_eq!(comments.inline(0x0800), None);
+        assert_eq!(comments.inline(0x0804), Some("out-of-range   commands"));
+        assert!(comments.contains(0x0804));
+        assert!(!comments.contains(0x0802));
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        let error = parse("0800 ok\nzz00 text\n").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(error.message.contains("invalid address"));
+        let error = parse("0800\n").unwrap_err();
+        assert_eq!(error.line, 1);
+        assert!(error.message.contains("missing comment"));
+    }
+
+    #[test]
+    fn moves_comments_after_existing_ones() {
+        let mut comments = parse("0800 load\n0801 high byte\n0801: note\n").unwrap();
+        comments.set_label(0x0801, "ptr".to_string());
+        comments.move_to(0x0801, 0x0800);
+        assert!(!comments.contains(0x0801));
+        assert_eq!(comments.inline(0x0800), Some("load; high byte"));
+        assert_eq!(comments.block(0x0800), ["note"]);
+        assert_eq!(comments.label(0x0800), Some("ptr"));
+    }
+}
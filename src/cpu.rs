@@ -0,0 +1,876 @@
+use crate::decoder::{self, DecodeError};
+use crate::instruction::{DecodedInstruction, DecodedOperand};
+use crate::ipl::{self, IPL_ADDR};
+use crate::spc::{Registers, Spc};
+
+/// PSW flag bits.
+pub mod flags {
+    pub const N: u8 = 0x80;
+    pub const V: u8 = 0x40;
+    pub const P: u8 = 0x20;
+    pub const B: u8 = 0x10;
+    pub const H: u8 = 0x08;
+    pub const I: u8 = 0x04;
+    pub const Z: u8 = 0x02;
+    pub const C: u8 = 0x01;
+}
+
+/// Extra cycles spent by a conditional branch when it is taken.
+pub const BRANCH_TAKEN_CYCLES: u8 = 2;
+
+const CONTROL: u16 = 0x00F1;
+const DSPADDR: u16 = 0x00F2;
+const DSPDATA: u16 = 0x00F3;
+
+/// One of the three S-SMP timers. T0 and T1 count at 8 kHz (every 128
+/// CPU cycles) and T2 at 64 kHz (every 16 cycles).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timer {
+    pub enabled: bool,
+    /// Divider from $FA-$FC; 0 means 256.
+    pub target: u8,
+    /// 4-bit output counter read (and cleared) through $FD-$FF.
+    pub output: u8,
+    stage: u8,
+    cycles: u32,
+}
+
+impl Timer {
+    fn tick(&mut self, cycles: u32, period: u32) {
+        self.cycles += cycles;
+        while self.cycles >= period {
+            self.cycles -= period;
+            if !self.enabled {
+                continue;
+            }
+            self.stage = self.stage.wrapping_add(1);
+            if self.stage == self.target {
+                self.stage = 0;
+                self.output = (self.output + 1) & 0x0F;
+            }
+        }
+    }
+
+    fn restart(&mut self) {
+        self.stage = 0;
+        self.output = 0;
+    }
+}
+
+/// An SPC700 interpreter running against an [`Spc`]'s memory.
+///
+/// Each [`step`](Cpu::step) executes one instruction decoded with
+/// [`decoder::decode_one`] and adds its cycle count from `OPCODES` (plus
+/// [`BRANCH_TAKEN_CYCLES`] for taken branches) to [`cycles`](Cpu::cycles).
+/// Reads and writes of $F0-$FF go to the timers, the CPU ports and the
+/// S-DSP registers in `Spc::dsp`.
+#[derive(Debug, Clone)]
+pub struct Cpu {
+    pub regs: Registers,
+    pub cycles: u64,
+    pub timers: [Timer; 3],
+    /// Values the S-CPU has written, returned by reads of $F4-$F7.
+    pub ports_in: [u8; 4],
+    /// Values the SPC700 has written to $F4-$F7.
+    pub ports_out: [u8; 4],
+    /// Set by `sleep` and `stop`; a halted CPU does not execute further.
+    pub halted: bool,
+}
+
+impl Cpu {
+    /// Creates a CPU with the registers in `spc.regs`, starting at `spc.pc`,
+    /// and the I/O state held in the snapshot's $F0-$FF bytes.
+    pub fn new(spc: &Spc) -> Self {
+        let control = spc.mem[CONTROL as usize];
+        let mut timers = [Timer::default(); 3];
+        for (i, timer) in timers.iter_mut().enumerate() {
+            timer.enabled = control & (1 << i) != 0;
+            timer.target = spc.mem[0xFA + i];
+            timer.output = spc.mem[0xFD + i] & 0x0F;
+        }
+        let mut ports_in = [0; 4];
+        ports_in.copy_from_slice(&spc.mem[0xF4..0xF8]);
+        Cpu {
+            regs: Registers {
+                pc: spc.pc,
+                ..spc.regs
+            },
+            cycles: 0,
+            timers,
+            ports_in,
+            ports_out: [0; 4],
+            halted: false,
+        }
+    }
+
+    pub fn flag(&self, flag: u8) -> bool {
+        self.regs.psw & flag != 0
+    }
+
+    pub fn set_flag(&mut self, flag: u8, value: bool) {
+        if value {
+            self.regs.psw |= flag;
+        } else {
+            self.regs.psw &= !flag;
+        }
+    }
+
+    /// Base address of the direct page selected by the P flag.
+    pub fn direct_page(&self) -> u16 {
+        if self.flag(flags::P) { 0x0100 } else { 0x0000 }
+    }
+
+    pub fn ya(&self) -> u16 {
+        u16::from_le_bytes([self.regs.a, self.regs.y])
+    }
+
+    fn set_ya(&mut self, value: u16) {
+        let [a, y] = value.to_le_bytes();
+        self.regs.a = a;
+        self.regs.y = y;
+    }
+
+    /// Decodes the instruction at PC without executing it.
+    pub fn next_instruction(&self, spc: &Spc) -> Result<DecodedInstruction, DecodeError> {
+        decoder::decode_one(spc, self.regs.pc)
+    }
+
+    /// Executes one instruction and returns the cycles it took. A halted CPU
+    /// returns 0 without doing anything.
+    pub fn step(&mut self, spc: &mut Spc) -> Result<u8, DecodeError> {
+        if self.halted {
+            return Ok(0);
+        }
+        let instr = self.next_instruction(spc)?;
+        self.regs.pc = instr.next_address();
+        let taken = self.execute(spc, &instr);
+        let mut cycles = instr.definition.cycles;
+        if taken {
+            cycles += BRANCH_TAKEN_CYCLES;
+        }
+        self.cycles += cycles as u64;
+        for (i, timer) in self.timers.iter_mut().enumerate() {
+            timer.tick(cycles as u32, if i == 2 { 16 } else { 128 });
+        }
+        Ok(cycles)
+    }
+
+    pub fn read(&mut self, spc: &mut Spc, addr: u16) -> u8 {
+        match addr {
+            0x00F0 | 0x00F1 | 0x00FA..=0x00FC => 0,
+            DSPDATA => spc.dsp[(spc.mem[DSPADDR as usize] & 0x7F) as usize],
+            0x00F4..=0x00F7 => self.ports_in[(addr - 0xF4) as usize],
+            0x00FD..=0x00FF => {
+                let timer = &mut self.timers[(addr - 0xFD) as usize];
+                let value = timer.output;
+                timer.output = 0;
+                value
+            }
+            _ => spc.mem[addr as usize],
+        }
+    }
+
+    pub fn write(&mut self, spc: &mut Spc, addr: u16, value: u8) {
+        match addr {
+            CONTROL => {
+                for (i, timer) in self.timers.iter_mut().enumerate() {
+                    let enable = value & (1 << i) != 0;
+                    if enable && !timer.enabled {
+                        timer.restart();
+                    }
+                    timer.enabled = enable;
+                }
+                if value & 0x10 != 0 {
+                    self.ports_in[0] = 0;
+                    self.ports_in[1] = 0;
+                }
+                if value & 0x20 != 0 {
+                    self.ports_in[2] = 0;
+                    self.ports_in[3] = 0;
+                }
+                if value & 0x80 != 0 {
+                    ipl::map_rom(spc);
+                } else {
+                    ipl::unmap_rom(spc);
+                }
+            }
+            DSPDATA => {
+                // DSP addresses $80-$FF mirror $00-$7F and are read-only.
+                let dsp_addr = spc.mem[DSPADDR as usize];
+                if dsp_addr < 0x80 {
+                    spc.dsp[dsp_addr as usize] = value;
+                }
+            }
+            0x00F4..=0x00F7 => self.ports_out[(addr - 0xF4) as usize] = value,
+            0x00FA..=0x00FC => self.timers[(addr - 0xFA) as usize].target = value,
+            // Writes under a mapped IPL ROM go to the RAM beneath it.
+            IPL_ADDR..=0xFFFF if spc.ipl_rom_mapped => {
+                spc.ipl_ram[(addr - IPL_ADDR) as usize] = value;
+                return;
+            }
+            _ => {}
+        }
+        // Writes to the I/O registers also land in the RAM underneath.
+        spc.mem[addr as usize] = value;
+    }
+
+    fn read_dp(&mut self, spc: &mut Spc, addr: u8) -> u8 {
+        self.read(spc, self.direct_page() | addr as u16)
+    }
+
+    fn write_dp(&mut self, spc: &mut Spc, addr: u8, value: u8) {
+        self.write(spc, self.direct_page() | addr as u16, value);
+    }
+
+    /// Reads a direct-page word; the high byte wraps within the page.
+    fn read_dp_word(&mut self, spc: &mut Spc, addr: u8) -> u16 {
+        let lo = self.read_dp(spc, addr);
+        let hi = self.read_dp(spc, addr.wrapping_add(1));
+        u16::from_le_bytes([lo, hi])
+    }
+
+    fn write_dp_word(&mut self, spc: &mut Spc, addr: u8, value: u16) {
+        let [lo, hi] = value.to_le_bytes();
+        self.write_dp(spc, addr, lo);
+        self.write_dp(spc, addr.wrapping_add(1), hi);
+    }
+
+    fn read_word(&mut self, spc: &mut Spc, addr: u16) -> u16 {
+        let lo = self.read(spc, addr);
+        let hi = self.read(spc, addr.wrapping_add(1));
+        u16::from_le_bytes([lo, hi])
+    }
+
+    fn push(&mut self, spc: &mut Spc, value: u8) {
+        self.write(spc, 0x0100 | self.regs.sp as u16, value);
+        self.regs.sp = self.regs.sp.wrapping_sub(1);
+    }
+
+    fn pop(&mut self, spc: &mut Spc) -> u8 {
+        self.regs.sp = self.regs.sp.wrapping_add(1);
+        self.read(spc, 0x0100 | self.regs.sp as u16)
+    }
+
+    fn push_pc(&mut self, spc: &mut Spc) {
+        let [lo, hi] = self.regs.pc.to_le_bytes();
+        self.push(spc, hi);
+        self.push(spc, lo);
+    }
+
+    fn pop_pc(&mut self, spc: &mut Spc) {
+        let lo = self.pop(spc);
+        let hi = self.pop(spc);
+        self.regs.pc = u16::from_le_bytes([lo, hi]);
+    }
+
+    /// Resolves the memory address an operand refers to.
+    fn effective_address(&mut self, spc: &mut Spc, operand: &DecodedOperand) -> u16 {
+        let dp = self.direct_page();
+        let x = self.regs.x;
+        let y = self.regs.y;
+        match *operand {
+            DecodedOperand::Direct(addr)
+            | DecodedOperand::DirectBit { addr }
+            | DecodedOperand::DirectBitRelative { addr, .. }
+            | DecodedOperand::DirectRelative { addr, .. }
+            | DecodedOperand::DpImm { addr, .. } => dp | addr as u16,
+            DecodedOperand::DirectX(addr) | DecodedOperand::DirectXRelative { addr, .. } => {
+                dp | addr.wrapping_add(x) as u16
+            }
+            DecodedOperand::DirectY(addr) => dp | addr.wrapping_add(y) as u16,
+            DecodedOperand::Absolute(addr) | DecodedOperand::MemoryBit { addr, .. } => addr,
+            DecodedOperand::AbsoluteX(addr) => addr.wrapping_add(x as u16),
+            DecodedOperand::AbsoluteY(addr) => addr.wrapping_add(y as u16),
+            DecodedOperand::IndirectX(addr) => self.read_dp_word(spc, addr.wrapping_add(x)),
+            DecodedOperand::IndirectY(addr) => self.read_dp_word(spc, addr).wrapping_add(y as u16),
+            DecodedOperand::Indirect | DecodedOperand::IndirectAutoInc => dp | x as u16,
+            _ => 0,
+        }
+    }
+
+    /// Reads the source value of a one-operand instruction: the immediate
+    /// byte or the byte at its effective address.
+    fn load(&mut self, spc: &mut Spc, instr: &DecodedInstruction) -> u8 {
+        match instr.operand {
+            DecodedOperand::Byte(value) => value,
+            DecodedOperand::IndirectAutoInc => {
+                let value = self.read(spc, self.direct_page() | self.regs.x as u16);
+                self.regs.x = self.regs.x.wrapping_add(1);
+                value
+            }
+            operand => {
+                let addr = self.effective_address(spc, &operand);
+                self.read(spc, addr)
+            }
+        }
+    }
+
+    fn store(&mut self, spc: &mut Spc, instr: &DecodedInstruction, value: u8) {
+        if let DecodedOperand::IndirectAutoInc = instr.operand {
+            self.write(spc, self.direct_page() | self.regs.x as u16, value);
+            self.regs.x = self.regs.x.wrapping_add(1);
+        } else {
+            let addr = self.effective_address(spc, &instr.operand);
+            self.write(spc, addr, value);
+        }
+    }
+
+    fn set_nz(&mut self, value: u8) {
+        self.set_flag(flags::N, value & 0x80 != 0);
+        self.set_flag(flags::Z, value == 0);
+    }
+
+    fn set_nz16(&mut self, value: u16) {
+        self.set_flag(flags::N, value & 0x8000 != 0);
+        self.set_flag(flags::Z, value == 0);
+    }
+
+    fn adc(&mut self, a: u8, b: u8) -> u8 {
+        let carry = self.flag(flags::C) as u16;
+        let result = a as u16 + b as u16 + carry;
+        let r = result as u8;
+        self.set_flag(flags::V, !(a ^ b) & (a ^ r) & 0x80 != 0);
+        self.set_flag(flags::H, (a ^ b ^ r) & 0x10 != 0);
+        self.set_flag(flags::C, result > 0xFF);
+        self.set_nz(r);
+        r
+    }
+
+    fn sbc(&mut self, a: u8, b: u8) -> u8 {
+        self.adc(a, !b)
+    }
+
+    fn cmp(&mut self, a: u8, b: u8) {
+        let result = a as i16 - b as i16;
+        self.set_flag(flags::C, result >= 0);
+        self.set_nz(result as u8);
+    }
+
+    /// Applies one of the two-operand ALU mnemonics and returns the result
+    /// to store, or `None` for `cmp`.
+    fn alu(&mut self, mnemonic: &str, a: u8, b: u8) -> Option<u8> {
+        let result = match mnemonic {
+            "or" => a | b,
+            "and" => a & b,
+            "eor" => a ^ b,
+            "adc" => return Some(self.adc(a, b)),
+            "sbc" => return Some(self.sbc(a, b)),
+            _ => {
+                self.cmp(a, b);
+                return None;
+            }
+        };
+        self.set_nz(result);
+        Some(result)
+    }
+
+    fn shift(&mut self, mnemonic: &str, value: u8) -> u8 {
+        let carry_in = self.flag(flags::C) as u8;
+        let (result, carry) = match mnemonic {
+            "asl" => (value << 1, value & 0x80 != 0),
+            "rol" => (value << 1 | carry_in, value & 0x80 != 0),
+            "lsr" => (value >> 1, value & 0x01 != 0),
+            _ => (value >> 1 | carry_in << 7, value & 0x01 != 0),
+        };
+        self.set_flag(flags::C, carry);
+        self.set_nz(result);
+        result
+    }
+
+    fn read_mem_bit(&mut self, spc: &mut Spc, operand: &DecodedOperand) -> bool {
+        let DecodedOperand::MemoryBit { addr, bit } = *operand else {
+            return false;
+        };
+        self.read(spc, addr) & (1 << bit) != 0
+    }
+
+    fn branch(&mut self, instr: &DecodedInstruction, condition: bool) -> bool {
+        if condition && let Some(target) = instr.branch_target() {
+            self.regs.pc = target;
+        }
+        condition
+    }
+
+    /// Executes `instr` with PC already advanced past it. Returns true if a
+    /// conditional branch was taken.
+    fn execute(&mut self, spc: &mut Spc, instr: &DecodedInstruction) -> bool {
+        let opcode = instr.definition.opcode;
+        let mnemonic = instr.definition.mnemonic;
+        let bit = opcode >> 5;
+
+        match opcode {
+            // Conditional branches on a flag.
+            0x10 => return self.branch(instr, !self.flag(flags::N)),
+            0x30 => return self.branch(instr, self.flag(flags::N)),
+            0x50 => return self.branch(instr, !self.flag(flags::V)),
+            0x70 => return self.branch(instr, self.flag(flags::V)),
+            0x90 => return self.branch(instr, !self.flag(flags::C)),
+            0xB0 => return self.branch(instr, self.flag(flags::C)),
+            0xD0 => return self.branch(instr, !self.flag(flags::Z)),
+            0xF0 => return self.branch(instr, self.flag(flags::Z)),
+            0x2F => {
+                self.branch(instr, true);
+            }
+
+            // bbs/bbc dp.bit,rel
+            op if op & 0x0F == 0x03 => {
+                let addr = self.effective_address(spc, &instr.operand);
+                let set = self.read(spc, addr) & (1 << bit) != 0;
+                return self.branch(instr, set == (op & 0x10 == 0));
+            }
+            0x2E | 0xDE => {
+                let addr = self.effective_address(spc, &instr.operand);
+                let value = self.read(spc, addr);
+                return self.branch(instr, self.regs.a != value);
+            }
+            0x6E => {
+                let addr = self.effective_address(spc, &instr.operand);
+                let value = self.read(spc, addr).wrapping_sub(1);
+                self.write(spc, addr, value);
+                return self.branch(instr, value != 0);
+            }
+            0xFE => {
+                self.regs.y = self.regs.y.wrapping_sub(1);
+                return self.branch(instr, self.regs.y != 0);
+            }
+
+            // Jumps, calls and returns.
+            0x5F => self.regs.pc = self.effective_address(spc, &instr.operand),
+            0x1F => {
+                let table = self.effective_address(spc, &instr.operand);
+                self.regs.pc = self.read_word(spc, table);
+            }
+            0x3F => {
+                self.push_pc(spc);
+                self.regs.pc = self.effective_address(spc, &instr.operand);
+            }
+            op if op & 0x0F == 0x01 => {
+                self.push_pc(spc);
+                let vector = 0xFFDE - 2 * (op >> 4) as u16;
+                self.regs.pc = self.read_word(spc, vector);
+            }
+            0x4F => {
+                self.push_pc(spc);
+                if let DecodedOperand::PCall(n) = instr.operand {
+                    self.regs.pc = 0xFF00 | n as u16;
+                }
+            }
+            0x0F => {
+                self.push_pc(spc);
+                self.push(spc, self.regs.psw);
+                self.set_flag(flags::B, true);
+                self.set_flag(flags::I, false);
+                self.regs.pc = self.read_word(spc, 0xFFDE);
+            }
+            0x6F => self.pop_pc(spc),
+            0x7F => {
+                self.regs.psw = self.pop(spc);
+                self.pop_pc(spc);
+            }
+
+            // Stack.
+            0x0D => self.push(spc, self.regs.psw),
+            0x2D => self.push(spc, self.regs.a),
+            0x4D => self.push(spc, self.regs.x),
+            0x6D => self.push(spc, self.regs.y),
+            0x8E => self.regs.psw = self.pop(spc),
+            0xAE => self.regs.a = self.pop(spc),
+            0xCE => self.regs.x = self.pop(spc),
+            0xEE => self.regs.y = self.pop(spc),
+
+            // Flag operations.
+            0x20 => self.set_flag(flags::P, false),
+            0x40 => self.set_flag(flags::P, true),
+            0x60 => self.set_flag(flags::C, false),
+            0x80 => self.set_flag(flags::C, true),
+            0xED => self.regs.psw ^= flags::C,
+            0xE0 => self.regs.psw &= !(flags::V | flags::H),
+            0xA0 => self.set_flag(flags::I, true),
+            0xC0 => self.set_flag(flags::I, false),
+            0xEF | 0xFF => {
+                // The CPU stays on the halting instruction.
+                self.regs.pc = instr.address;
+                self.halted = true;
+            }
+            0x00 => {}
+
+            // set1/clr1 dp.bit
+            op if op & 0x0F == 0x02 => {
+                let addr = self.effective_address(spc, &instr.operand);
+                let value = self.read(spc, addr);
+                let value = if op & 0x10 == 0 {
+                    value | 1 << bit
+                } else {
+                    value & !(1 << bit)
+                };
+                self.write(spc, addr, value);
+            }
+            0x0E | 0x4E => {
+                let addr = self.effective_address(spc, &instr.operand);
+                let value = self.read(spc, addr);
+                self.set_nz(self.regs.a.wrapping_sub(value));
+                let value = if opcode == 0x0E {
+                    value | self.regs.a
+                } else {
+                    value & !self.regs.a
+                };
+                self.write(spc, addr, value);
+            }
+
+            // Memory bit operations on the carry.
+            0x0A | 0x2A | 0x4A | 0x6A | 0x8A => {
+                let mut value = self.read_mem_bit(spc, &instr.operand);
+                if opcode & 0x20 != 0 {
+                    value = !value;
+                }
+                let carry = self.flag(flags::C);
+                let result = match mnemonic {
+                    "or1" => carry | value,
+                    "and1" => carry & value,
+                    _ => carry ^ value,
+                };
+                self.set_flag(flags::C, result);
+            }
+            0xAA => {
+                let value = self.read_mem_bit(spc, &instr.operand);
+                self.set_flag(flags::C, value);
+            }
+            0xCA | 0xEA => {
+                if let DecodedOperand::MemoryBit { addr, bit } = instr.operand {
+                    let value = self.read(spc, addr);
+                    let value = if opcode == 0xEA {
+                        value ^ 1 << bit
+                    } else if self.flag(flags::C) {
+                        value | 1 << bit
+                    } else {
+                        value & !(1 << bit)
+                    };
+                    self.write(spc, addr, value);
+                }
+            }
+
+            // 16-bit operations.
+            0xBA => {
+                let DecodedOperand::Direct(addr) = instr.operand else {
+                    return false;
+                };
+                let value = self.read_dp_word(spc, addr);
+                self.set_ya(value);
+                self.set_nz16(value);
+            }
+            0xDA => {
+                if let DecodedOperand::Direct(addr) = instr.operand {
+                    self.write_dp_word(spc, addr, self.ya());
+                }
+            }
+            0x1A | 0x3A => {
+                let DecodedOperand::Direct(addr) = instr.operand else {
+                    return false;
+                };
+                let value = self.read_dp_word(spc, addr);
+                let value = if opcode == 0x3A {
+                    value.wrapping_add(1)
+                } else {
+                    value.wrapping_sub(1)
+                };
+                self.write_dp_word(spc, addr, value);
+                self.set_nz16(value);
+            }
+            0x7A | 0x9A => {
+                let DecodedOperand::Direct(addr) = instr.operand else {
+                    return false;
+                };
+                let [lo, hi] = self.read_dp_word(spc, addr).to_le_bytes();
+                // Two 8-bit steps through the ALU; Z reflects the whole word.
+                if opcode == 0x7A {
+                    self.set_flag(flags::C, false);
+                    self.regs.a = self.adc(self.regs.a, lo);
+                    self.regs.y = self.adc(self.regs.y, hi);
+                } else {
+                    self.set_flag(flags::C, true);
+                    self.regs.a = self.sbc(self.regs.a, lo);
+                    self.regs.y = self.sbc(self.regs.y, hi);
+                }
+                self.set_flag(flags::Z, self.ya() == 0);
+            }
+            0x5A => {
+                let DecodedOperand::Direct(addr) = instr.operand else {
+                    return false;
+                };
+                let value = self.read_dp_word(spc, addr);
+                let result = self.ya() as i32 - value as i32;
+                self.set_flag(flags::C, result >= 0);
+                self.set_nz16(result as u16);
+            }
+            0xCF => {
+                let result = self.regs.y as u16 * self.regs.a as u16;
+                self.set_ya(result);
+                self.set_nz(self.regs.y);
+            }
+            0x9E => self.div(),
+
+            // Decimal adjust and nibble swap.
+            0xDF => {
+                let mut a = self.regs.a;
+                if self.flag(flags::C) || a > 0x99 {
+                    a = a.wrapping_add(0x60);
+                    self.set_flag(flags::C, true);
+                }
+                if self.flag(flags::H) || a & 0x0F > 0x09 {
+                    a = a.wrapping_add(0x06);
+                }
+                self.regs.a = a;
+                self.set_nz(a);
+            }
+            0xBE => {
+                let mut a = self.regs.a;
+                if !self.flag(flags::C) || a > 0x99 {
+                    a = a.wrapping_sub(0x60);
+                    self.set_flag(flags::C, false);
+                }
+                if !self.flag(flags::H) || a & 0x0F > 0x09 {
+                    a = a.wrapping_sub(0x06);
+                }
+                self.regs.a = a;
+                self.set_nz(a);
+            }
+            0x9F => {
+                self.regs.a = self.regs.a.rotate_left(4);
+                self.set_nz(self.regs.a);
+            }
+
+            // Register transfers.
+            0x5D | 0x7D | 0x9D | 0xDD | 0xFD | 0xBD => {
+                let (value, dest) = match opcode {
+                    0x5D => (self.regs.a, &mut self.regs.x),
+                    0x7D => (self.regs.x, &mut self.regs.a),
+                    0x9D => (self.regs.sp, &mut self.regs.x),
+                    0xDD => (self.regs.y, &mut self.regs.a),
+                    0xFD => (self.regs.a, &mut self.regs.y),
+                    _ => (self.regs.x, &mut self.regs.sp),
+                };
+                *dest = value;
+                // mov sp,x leaves the flags alone.
+                if opcode != 0xBD {
+                    self.set_nz(value);
+                }
+            }
+
+            // Stores: mov dest,reg never touches the flags.
+            0xC4 | 0xD4 | 0xC5 | 0xD5 | 0xD6 | 0xC6 | 0xAF | 0xC7 | 0xD7 => {
+                self.store(spc, instr, self.regs.a)
+            }
+            0xD8 | 0xD9 | 0xC9 => self.store(spc, instr, self.regs.x),
+            0xCB | 0xDB | 0xCC => self.store(spc, instr, self.regs.y),
+            0xFA => {
+                if let DecodedOperand::DpToDp { dest, src } = instr.operand {
+                    let value = self.read_dp(spc, src);
+                    self.write_dp(spc, dest, value);
+                }
+            }
+            0x8F => {
+                if let DecodedOperand::DpImm { addr, imm } = instr.operand {
+                    self.write_dp(spc, addr, imm);
+                }
+            }
+
+            // Loads.
+            _ if mnemonic == "mov" => {
+                let value = self.load(spc, instr);
+                match opcode {
+                    0xCD | 0xF8 | 0xF9 | 0xE9 => self.regs.x = value,
+                    0x8D | 0xEB | 0xFB | 0xEC => self.regs.y = value,
+                    _ => self.regs.a = value,
+                }
+                self.set_nz(value);
+            }
+
+            // Read-modify-write on a register or memory.
+            _ if matches!(mnemonic, "asl" | "rol" | "lsr" | "ror" | "inc" | "dec") => {
+                let step = |cpu: &mut Cpu, value: u8| match mnemonic {
+                    "inc" | "dec" => {
+                        let result = if mnemonic == "inc" {
+                            value.wrapping_add(1)
+                        } else {
+                            value.wrapping_sub(1)
+                        };
+                        cpu.set_nz(result);
+                        result
+                    }
+                    _ => cpu.shift(mnemonic, value),
+                };
+                match opcode {
+                    0x1D | 0x3D => self.regs.x = step(self, self.regs.x),
+                    0xDC | 0xFC => self.regs.y = step(self, self.regs.y),
+                    op if op & 0x0F == 0x0C && op & 0x10 != 0 => {
+                        self.regs.a = step(self, self.regs.a)
+                    }
+                    _ => {
+                        let addr = self.effective_address(spc, &instr.operand);
+                        let value = self.read(spc, addr);
+                        let result = step(self, value);
+                        self.write(spc, addr, result);
+                    }
+                }
+            }
+
+            // or/and/eor/cmp/adc/sbc.
+            _ => match instr.operand {
+                DecodedOperand::DpToDp { dest, src } => {
+                    let b = self.read_dp(spc, src);
+                    let a = self.read_dp(spc, dest);
+                    if let Some(result) = self.alu(mnemonic, a, b) {
+                        self.write_dp(spc, dest, result);
+                    }
+                }
+                DecodedOperand::DpImm { addr, imm } => {
+                    let a = self.read_dp(spc, addr);
+                    if let Some(result) = self.alu(mnemonic, a, imm) {
+                        self.write_dp(spc, addr, result);
+                    }
+                }
+                DecodedOperand::None => {
+                    let x_addr = self.direct_page() | self.regs.x as u16;
+                    let y_addr = self.direct_page() | self.regs.y as u16;
+                    let b = self.read(spc, y_addr);
+                    let a = self.read(spc, x_addr);
+                    if let Some(result) = self.alu(mnemonic, a, b) {
+                        self.write(spc, x_addr, result);
+                    }
+                }
+                _ => {
+                    let b = self.load(spc, instr);
+                    match opcode {
+                        0xC8 | 0x3E | 0x1E => self.cmp(self.regs.x, b),
+                        0xAD | 0x7E | 0x5E => self.cmp(self.regs.y, b),
+                        _ => {
+                            if let Some(result) = self.alu(mnemonic, self.regs.a, b) {
+                                self.regs.a = result;
+                            }
+                        }
+                    }
+                }
+            },
+        }
+        false
+    }
+
+    /// YA / X, with the hardware's behaviour when the quotient overflows.
+    fn div(&mut self) {
+        let ya = self.ya() as u32;
+        let y = self.regs.y as u32;
+        let x = self.regs.x as u32;
+        self.set_flag(flags::H, (y & 0x0F) >= (x & 0x0F));
+        self.set_flag(flags::V, y >= x);
+        if y < x << 1 {
+            self.regs.a = (ya / x) as u8;
+            self.regs.y = (ya % x) as u8;
+        } else {
+            let rest = ya - (x << 9);
+            self.regs.a = (255 - rest / (256 - x)) as u8;
+            self.regs.y = (x + rest % (256 - x)) as u8;
+        }
+        self.set_nz(self.regs.a);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spc::Config;
+
+    fn run(code: &[u8], setup: impl FnOnce(&mut Cpu)) -> (Cpu, Spc) {
+        let mut spc = Spc::new(Config::default(), 0x0200, 0x0200);
+        spc.load_rom(code, 0x0200);
+        let mut cpu = Cpu::new(&spc);
+        setup(&mut cpu);
+        let end = 0x0200 + code.len() as u16;
+        while cpu.regs.pc != end && !cpu.halted {
+            cpu.step(&mut spc).unwrap();
+        }
+        (cpu, spc)
+    }
+
+    #[test]
+    fn div_matches_hardware_on_overflow() {
+        let (cpu, _) = run(&[0x9E], |cpu| {
+            cpu.regs.a = 0x34;
+            cpu.regs.y = 0x12;
+            cpu.regs.x = 0x10;
+        });
+        assert_eq!((cpu.regs.a, cpu.regs.y), (0x23, 0x04));
+        assert!(cpu.flag(flags::V));
+
+        let (cpu, _) = run(&[0x9E], |cpu| {
+            cpu.regs.a = 0x00;
+            cpu.regs.y = 0x00;
+            cpu.regs.x = 0x00;
+        });
+        assert_eq!((cpu.regs.a, cpu.regs.y), (0xFF, 0x00));
+        assert_eq!(cpu.cycles, 12);
+    }
+
+    #[test]
+    fn decimal_adjust_after_addition_and_subtraction() {
+        // clrc; mov a,#$19; adc a,#$28; daa
+        let (cpu, _) = run(&[0x60, 0xE8, 0x19, 0x88, 0x28, 0xDF], |_| {});
+        assert_eq!(cpu.regs.a, 0x47);
+        assert!(!cpu.flag(flags::C));
+
+        // setc; mov a,#$42; sbc a,#$15; das
+        let (cpu, _) = run(&[0x80, 0xE8, 0x42, 0xA8, 0x15, 0xBE], |_| {});
+        assert_eq!(cpu.regs.a, 0x27);
+        assert!(cpu.flag(flags::C));
+    }
+
+    #[test]
+    fn mul_sets_flags_from_y() {
+        let (cpu, _) = run(&[0xCF], |cpu| {
+            cpu.regs.a = 0x80;
+            cpu.regs.y = 0x03;
+        });
+        assert_eq!(cpu.ya(), 0x0180);
+        assert!(!cpu.flag(flags::N) && !cpu.flag(flags::Z));
+        assert_eq!(cpu.cycles, 9);
+    }
+
+    #[test]
+    fn taken_branches_cost_two_more_cycles() {
+        // mov $10,#$01; bbs0 $10,+0; bbc0 $10,+0; cbne $10,+0
+        let code = [
+            0x8F, 0x01, 0x10, 0x03, 0x10, 0x00, 0x13, 0x10, 0x00, 0x2E, 0x10, 0x00,
+        ];
+        let (cpu, _) = run(&code, |cpu| cpu.regs.a = 0x01);
+        assert_eq!(cpu.cycles, 5 + 7 + 5 + 5);
+
+        // mov y,#$03; dbnz y,-2 loops three times
+        let (cpu, _) = run(&[0x8D, 0x03, 0xFE, 0xFE], |_| {});
+        assert_eq!(cpu.regs.y, 0);
+        assert_eq!(cpu.cycles, 2 + 6 + 6 + 4);
+    }
+
+    #[test]
+    fn dsp_registers_are_written_through_dspaddr() {
+        // mov $f2,#$4c; mov a,#$01; mov $f3,a
+        let (_, spc) = run(&[0x8F, 0x4C, 0xF2, 0xE8, 0x01, 0xC4, 0xF3], |_| {});
+        assert_eq!(spc.dsp[0x4C], 0x01);
+    }
+
+    #[test]
+    fn control_bit_7_maps_the_ipl_rom() {
+        // mov $f1,#$80; mov $ffc0,a; mov $f1,#$00
+        let (_, spc) = run(&[0x8F, 0x80, 0xF1, 0xC5, 0xC0, 0xFF], |cpu| {
+            cpu.regs.a = 0x42
+        });
+        assert!(spc.ipl_rom_mapped);
+        assert_eq!(spc.mem[0xFFC0..], ipl::IPL_ROM);
+        assert_eq!(spc.ipl_ram[0], 0x42);
+
+        let (_, spc) = run(
+            &[0x8F, 0x80, 0xF1, 0xC5, 0xC0, 0xFF, 0x8F, 0x00, 0xF1],
+            |cpu| cpu.regs.a = 0x42,
+        );
+        assert!(!spc.ipl_rom_mapped);
+        assert_eq!(spc.mem[0xFFC0], 0x42);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::parse_addr;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// How the bytes of a data region are listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataKind {
+    /// `db $xx,...`
+    Bytes,
+    /// `dw $xxxx,...`
+    Words,
+    /// `dw` entries shown as labels where the address has one.
+    Pointers,
+    /// `db "text",$xx` with printable ASCII quoted.
+    Text,
+}
+
+impl DataKind {
+    /// Most bytes put on one listing line.
+    pub fn bytes_per_line(self) -> u16 {
+        match self {
+            DataKind::Bytes | DataKind::Words | DataKind::Pointers => 8,
+            DataKind::Text => 32,
+        }
+    }
+}
+
+impl FromStr for DataKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "byte" | "bytes" | "db" => Ok(DataKind::Bytes),
+            "word" | "words" | "dw" => Ok(DataKind::Words),
+            "ptr" | "pointer" | "pointers" => Ok(DataKind::Pointers),
+            "text" | "ascii" => Ok(DataKind::Text),
+            _ => Err(format!(
+                "unknown data kind '{}' (expected byte, word, ptr or text)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DataKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DataKind::Bytes => "byte",
+            DataKind::Words => "word",
+            DataKind::Pointers => "ptr",
+            DataKind::Text => "text",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The addresses `start..end` (wrapping at $FFFF) hold data of one kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataRegion {
+    pub start: u16,
+    pub end: u16,
+    pub kind: DataKind,
+}
+
+impl DataRegion {
+    /// A pointer table of `count` entries at `start`.
+    pub fn jump_table(start: u16, count: u16) -> Self {
+        DataRegion {
+            start,
+            end: start.wrapping_add(count.wrapping_mul(2)),
+            kind: DataKind::Pointers,
+        }
+    }
+
+    pub fn contains(&self, addr: u16) -> bool {
+        addr.wrapping_sub(self.start) < self.end.wrapping_sub(self.start)
+    }
+
+    /// Bytes from `addr` to the end of the region.
+    pub fn remaining(&self, addr: u16) -> u16 {
+        self.end.wrapping_sub(addr)
+    }
+}
+
+impl FromStr for DataRegion {
+    type Err = String;
+
+    /// Parses `START:END[:KIND]` with hex addresses; `KIND` defaults to
+    /// bytes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let mut addr = |what: &str| {
+            let part = parts
+                .next()
+                .filter(|p| !p.is_empty())
+                .ok_or_else(|| format!("missing {} address in '{}'", what, s))?;
+            parse_addr(part).map_err(|e| format!("invalid {} address '{}': {}", what, part, e))
+        };
+        let start = addr("start")?;
+        let end = addr("end")?;
+        let kind = match parts.next() {
+            Some(kind) => kind.parse()?,
+            None => DataKind::Bytes,
+        };
+        if parts.next().is_some() {
+            return Err(format!("expected START:END[:KIND] but found '{}'", s));
+        }
+        Ok(DataRegion { start, end, kind })
+    }
+}
+
+/// Parses a `--jump-table` hint, `ADDR:COUNT` with a hex table address and
+/// a decimal entry count, into a pointer region.
+pub fn parse_jump_table(spec: &str) -> Result<DataRegion, String> {
+    let (addr, count) = spec
+        .split_once(':')
+        .ok_or_else(|| format!("expected ADDR:COUNT but found '{}'", spec))?;
+    let start = parse_addr(addr).map_err(|e| format!("invalid table address '{}': {}", addr, e))?;
+    let count: u16 = count
+        .parse()
+        .ok()
+        .filter(|&n| (1..=0x8000).contains(&n))
+        .ok_or_else(|| format!("invalid entry count '{}'", count))?;
+    Ok(DataRegion::jump_table(start, count))
+}
+
+/// User-declared data regions, keyed by start address.
+#[derive(Debug, Default)]
+pub struct DataMap {
+    regions: BTreeMap<u16, DataRegion>,
+}
+
+impl DataMap {
+    /// Adds `region`, replacing any region with the same start.
+    pub fn insert(&mut self, region: DataRegion) {
+        self.regions.insert(region.start, region);
+    }
+
+    /// The region covering `addr`; later starts win where regions overlap.
+    pub fn region_at(&self, addr: u16) -> Option<&DataRegion> {
+        self.regions
+            .values()
+            .rev()
+            .find(|region| region.contains(addr))
+    }
+
+    pub fn contains(&self, addr: u16) -> bool {
+        self.region_at(addr).is_some()
+    }
+
+    /// Offset of the first of the `len` bytes from `addr` that is in a
+    /// region.
+    pub fn first_in(&self, addr: u16, len: u16) -> Option<u16> {
+        (0..len).find(|&i| self.contains(addr.wrapping_add(i)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DataRegion> {
+        self.regions.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(start: u16, end: u16, kind: DataKind) -> DataRegion {
+        DataRegion { start, end, kind }
+    }
+
+    #[test]
+    fn parses_regions() {
+        assert_eq!(
+            "0800:0810".parse(),
+            Ok(region(0x0800, 0x0810, DataKind::Bytes))
+        );
+        assert_eq!(
+            "$0800:0x0810:ptr".parse(),
+            Ok(region(0x0800, 0x0810, DataKind::Pointers))
+        );
+        assert_eq!(
+            "fff0:0010:TEXT".parse(),
+            Ok(region(0xFFF0, 0x0010, DataKind::Text))
+        );
+        for bad in [
+            "0800",
+            "0800:",
+            ":0810",
+            "0800:zz",
+            "0800:0810:float",
+            "0800:0810:db:x",
+        ] {
+            assert!(bad.parse::<DataRegion>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn parses_jump_tables() {
+        assert_eq!(
+            parse_jump_table("0a40:12"),
+            Ok(region(0x0A40, 0x0A58, DataKind::Pointers))
+        );
+        for bad in ["0a40", "0a40:0", "0a40:x", "0a40:40000", "zz:2"] {
+            assert!(parse_jump_table(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn bytes_per_line() {
+        assert_eq!(DataKind::Bytes.bytes_per_line(), 8);
+        assert_eq!(DataKind::Words.bytes_per_line(), 8);
+        assert_eq!(DataKind::Pointers.bytes_per_line(), 8);
+        assert_eq!(DataKind::Text.bytes_per_line(), 32);
+    }
+
+    #[test]
+    fn regions_wrap_and_later_starts_win() {
+        let wrapped = region(0xFFFE, 0x0002, DataKind::Bytes);
+        assert!(wrapped.contains(0xFFFF) && wrapped.contains(0x0001));
+        assert!(!wrapped.contains(0x0002) && !wrapped.contains(0xFFFD));
+        assert_eq!(wrapped.remaining(0xFFFF), 3);
+
+        let mut map = DataMap::default();
+        map.insert(region(0x0800, 0x0900, DataKind::Bytes));
+        map.insert(region(0x0840, 0x0850, DataKind::Words));
+        assert_eq!(map.region_at(0x0844).unwrap().kind, DataKind::Words);
+        assert_eq!(map.region_at(0x0850).unwrap().kind, DataKind::Bytes);
+        assert!(map.region_at(0x0900).is_none());
+        assert_eq!(map.first_in(0x07FE, 4), Some(2));
+        assert_eq!(map.first_in(0x07F0, 4), None);
+    }
+}
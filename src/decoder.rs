@@ -1,23 +1,80 @@
+use crate::disassembler::Disassembler;
 use crate::instruction::{self, AddressingModeKind, DecodedInstruction, DecodedOperand};
-use crate::spc::Spc;
+use crate::memory::Memory;
+use std::fmt;
 
 #[derive(Debug)]
-pub struct DecodeError {
-    pub pc: u16,
-    pub opcode: u8,
+pub enum DecodeError {
+    InvalidOpcode {
+        pc: u16,
+        opcode: u8,
+    },
+    /// A byte of the instruction at `pc` is not known to the memory.
+    UnknownByte {
+        pc: u16,
+        addr: u16,
+    },
 }
 
-pub fn decode_one(spc: &Spc, pc: u16) -> Result<DecodedInstruction, DecodeError> {
-    let opcode = spc.read_byte(pc);
+impl DecodeError {
+    /// Address of the instruction that failed to decode.
+    pub fn pc(&self) -> u16 {
+        match *self {
+            DecodeError::InvalidOpcode { pc, .. } | DecodeError::UnknownByte { pc, .. } => pc,
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode ${:02x} at ${:04x}", opcode, pc)
+            }
+            DecodeError::UnknownByte { pc, addr } => write!(
+                f,
+                "unknown byte at ${:04x} in instruction at ${:04x}",
+                addr, pc
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Decodes consecutive instructions from `start` up to `end`, or the whole
+/// address space when they are equal; see
+/// [`Disassembler`].
+pub fn decode_range<M: Memory + ?Sized>(mem: &M, start: u16, end: u16) -> Disassembler<'_, M> {
+    Disassembler::new(mem, start, end)
+}
+
+pub fn decode_one<M: Memory + ?Sized>(mem: &M, pc: u16) -> Result<DecodedInstruction, DecodeError> {
+    let opcode = mem
+        .read(pc)
+        .ok_or(DecodeError::UnknownByte { pc, addr: pc })?;
     let definition = &instruction::OPCODES[opcode as usize];
 
     if let AddressingModeKind::Invalid = definition.mode {
-        return Err(DecodeError { pc, opcode });
+        return Err(DecodeError::InvalidOpcode { pc, opcode });
     }
 
-    let operand_b = || spc.read_byte(pc.wrapping_add(1));
-    let operand_w = || spc.read_word(pc.wrapping_add(1));
-    let operand2_b = || spc.read_byte(pc.wrapping_add(2));
+    let mut bytes = [opcode, 0, 0];
+    for (i, byte) in bytes
+        .iter_mut()
+        .enumerate()
+        .take(definition.len as usize)
+        .skip(1)
+    {
+        let addr = pc.wrapping_add(i as u16);
+        *byte = mem
+            .read(addr)
+            .ok_or(DecodeError::UnknownByte { pc, addr })?;
+    }
+
+    let operand_b = || bytes[1];
+    let operand_w = || u16::from_le_bytes([bytes[1], bytes[2]]);
+    let operand2_b = || bytes[2];
 
     let operand = match definition.mode {
         AddressingModeKind::Implied | AddressingModeKind::Brk => DecodedOperand::None,
@@ -48,8 +105,9 @@ pub fn decode_one(spc: &Spc, pc: u16) -> Result<DecodedInstruction, DecodeError>
         },
         AddressingModeKind::MemoryBit | AddressingModeKind::MemoryBitNegated => {
             let addr_word = operand_w();
-            let addr = addr_word >> 3;
-            let bit = (addr_word & 0x07) as u8;
+            // The low 13 bits hold the address and the top 3 the bit number.
+            let addr = addr_word & 0x1FFF;
+            let bit = (addr_word >> 13) as u8;
             DecodedOperand::MemoryBit { addr, bit }
         }
 
@@ -79,3 +137,28 @@ pub fn decode_one(spc: &Spc, pc: u16) -> Result<DecodedInstruction, DecodeError>
         operand,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::SliceMemory;
+
+    #[test]
+    fn memory_bit_operands_hold_a_13_bit_address_and_3_bit_number() {
+        for (bytes, addr, bit) in [
+            ([0x4A, 0x34, 0xB2], 0x1234, 5), // and1 c,$1234,5
+            ([0x6A, 0xFF, 0xFF], 0x1FFF, 7), // and1 c,!($1fff,7)
+            ([0xAA, 0x00, 0x00], 0x0000, 0), // mov1 c,$0000,0
+            ([0xCA, 0xF7, 0xCA], 0x0AF7, 6), // mov1 $0af7,6,c
+            ([0xEA, 0x12, 0x20], 0x0012, 1), // not1 $0012,1
+        ] {
+            let instr = decode_one(&SliceMemory::new(&bytes, 0x0200), 0x0200).unwrap();
+            assert!(
+                matches!(instr.operand, DecodedOperand::MemoryBit { addr: a, bit: b } if a == addr && b == bit),
+                "{:02x?} decoded as {:?}",
+                bytes,
+                instr.operand
+            );
+        }
+    }
+}
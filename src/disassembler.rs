@@ -0,0 +1,131 @@
+use crate::decoder::{self, DecodeError};
+use crate::instruction::DecodedInstruction;
+use crate::memory::Memory;
+
+/// Iterator over the instructions in an address range.
+///
+/// Addresses wrap from $FFFF to $0000, and a range that ends where it starts
+/// covers the whole 64 KiB address space. An instruction that starts inside the
+/// range is yielded even if its operand bytes run past the end. After an
+/// invalid opcode, decoding resumes at the next byte.
+#[derive(Debug)]
+pub struct Disassembler<'a, M: Memory + ?Sized> {
+    mem: &'a M,
+    pc: u16,
+    start: u16,
+    len: usize,
+    remaining: usize,
+}
+
+impl<'a, M: Memory + ?Sized> Disassembler<'a, M> {
+    /// Walks `start..end`; `start == end` walks all 64 KiB.
+    pub fn new(mem: &'a M, start: u16, end: u16) -> Self {
+        let len = match end.wrapping_sub(start) {
+            0 => 0x10000,
+            len => len as usize,
+        };
+        Self::with_len(mem, start, len)
+    }
+
+    /// Walks `len` bytes from `start`, up to the whole 64 KiB address space.
+    pub fn with_len(mem: &'a M, start: u16, len: usize) -> Self {
+        let len = len.min(0x10000);
+        Disassembler {
+            mem,
+            pc: start,
+            start,
+            len,
+            remaining: len,
+        }
+    }
+
+    /// Address of the next instruction to be decoded.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Number of bytes left before the end of the range.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Continues decoding from `addr`, keeping the end of the range. An
+    /// address outside the range leaves nothing to decode.
+    pub fn seek(&mut self, addr: u16) {
+        let offset = addr.wrapping_sub(self.start) as usize;
+        self.pc = addr;
+        self.remaining = self.len.saturating_sub(offset);
+    }
+}
+
+impl<M: Memory + ?Sized> Iterator for Disassembler<'_, M> {
+    type Item = Result<DecodedInstruction, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let result = decoder::decode_one(self.mem, self.pc);
+        let len = match &result {
+            Ok(instr) => instr.definition.len as usize,
+            Err(_) => 1,
+        };
+        self.pc = self.pc.wrapping_add(len as u16);
+        self.remaining = self.remaining.saturating_sub(len);
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_start_and_end_walk_the_whole_address_space() {
+        // All nops, so every address starts an instruction.
+        let mem = vec![0x00; 0x10000];
+        let disasm = Disassembler::new(mem.as_slice(), 0x0800, 0x0800);
+        assert_eq!(disasm.remaining(), 0x10000);
+        let addrs: Vec<u16> = disasm.map(|instr| instr.unwrap().address).collect();
+        assert_eq!(addrs.len(), 0x10000);
+        assert_eq!(
+            (addrs[0], addrs[0xF800], addrs[0xFFFF]),
+            (0x0800, 0x0000, 0x07FF)
+        );
+
+        assert_eq!(
+            Disassembler::new(mem.as_slice(), 0x0000, 0x0000).count(),
+            0x10000
+        );
+        assert_eq!(Disassembler::with_len(mem.as_slice(), 0x0000, 0).count(), 0);
+    }
+
+    #[test]
+    fn wraps_past_ffff() {
+        let mem = vec![0x00; 0x10000];
+        let addrs: Vec<u16> = Disassembler::new(mem.as_slice(), 0xFFFE, 0x0002)
+            .map(|instr| instr.unwrap().address)
+            .collect();
+        assert_eq!(addrs, [0xFFFE, 0xFFFF, 0x0000, 0x0001]);
+    }
+
+    #[test]
+    fn seek_stays_within_the_range() {
+        let mem = vec![0x00; 0x10000];
+        let mut disasm = Disassembler::new(mem.as_slice(), 0x0800, 0x0810);
+        disasm.seek(0x080C);
+        assert_eq!(disasm.remaining(), 4);
+        assert_eq!(disasm.next().unwrap().unwrap().address, 0x080C);
+
+        disasm.seek(0x0810);
+        assert_eq!(disasm.remaining(), 0);
+        disasm.seek(0x0900);
+        assert_eq!(disasm.remaining(), 0);
+        assert!(disasm.next().is_none());
+        disasm.seek(0x07FF);
+        assert_eq!(disasm.remaining(), 0);
+
+        disasm.seek(0x0800);
+        assert_eq!(disasm.count(), 16);
+    }
+}
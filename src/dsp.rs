@@ -0,0 +1,235 @@
+use crate::instruction::{DecodedInstruction, DecodedOperand};
+
+const DSPADDR: u16 = 0x00F2;
+const DSPDATA: u16 = 0x00F3;
+
+const VOICE_REGS: [&str; 10] = [
+    "VOLL", "VOLR", "PITCHL", "PITCHH", "SRCN", "ADSR1", "ADSR2", "GAIN", "ENVX", "OUTX",
+];
+const GLOBAL_REGS_C: [&str; 8] = [
+    "MVOLL", "MVOLR", "EVOLL", "EVOLR", "KON", "KOFF", "FLG", "ENDX",
+];
+const GLOBAL_REGS_D: [&str; 8] = ["EFB", "", "PMON", "NON", "EON", "DIR", "ESA", "EDL"];
+
+/// Name of the S-DSP register selected by writing `addr` to DSPADDR.
+/// Addresses $80-$FF mirror $00-$7F read-only.
+pub fn register_name(addr: u8) -> String {
+    let reg = addr & 0x7F;
+    let (hi, lo) = ((reg >> 4) as usize, (reg & 0x0F) as usize);
+    let name = match lo {
+        0x0..=0x9 => format!("V{}{}", hi, VOICE_REGS[lo]),
+        0xC => GLOBAL_REGS_C[hi].to_string(),
+        0xD if !GLOBAL_REGS_D[hi].is_empty() => GLOBAL_REGS_D[hi].to_string(),
+        0xF => format!("FIR{}", hi),
+        _ => format!("${:02x} (unused)", reg),
+    };
+    if addr & 0x80 != 0 {
+        format!("{} (read-only mirror)", name)
+    } else {
+        name
+    }
+}
+
+/// Follows the constant values of A, X, Y and DSPADDR through a basic block
+/// so DSPDATA accesses can be named.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DspTracker {
+    a: Option<u8>,
+    x: Option<u8>,
+    y: Option<u8>,
+    dspaddr: Option<u8>,
+}
+
+impl DspTracker {
+    /// Forgets everything; called at the start of every basic block.
+    pub fn reset(&mut self) {
+        *self = DspTracker::default();
+    }
+
+    /// Replaces the tracked values with known ones, e.g. the live state of
+    /// a running CPU.
+    pub fn set_known(&mut self, a: u8, x: u8, y: u8, dspaddr: u8) {
+        *self = DspTracker {
+            a: Some(a),
+            x: Some(x),
+            y: Some(y),
+            dspaddr: Some(dspaddr),
+        };
+    }
+
+    /// Describes the S-DSP register accessed by `instr`, given the state
+    /// before it executes.
+    pub fn annotate(&self, instr: &DecodedInstruction, direct_page: u16) -> Option<String> {
+        // movw $f2,ya writes A to DSPADDR and Y to DSPDATA in one go.
+        if instr.definition.opcode == 0xDA
+            && direct_page == 0
+            && let DecodedOperand::Direct(0xF2) = instr.operand
+        {
+            return Some(describe(self.a?, self.y));
+        }
+
+        if !instr.operand_addresses(direct_page).contains(&DSPDATA) {
+            return None;
+        }
+        let dspaddr = self.dspaddr?;
+        let value = match (instr.definition.opcode, instr.operand) {
+            (0x8F, DecodedOperand::DpImm { imm, .. }) => Some(imm),
+            (0xC4 | 0xC5, _) => self.a,
+            (0xD8 | 0xC9, _) => self.x,
+            (0xCB | 0xCC, _) => self.y,
+            _ => None,
+        };
+        Some(describe(dspaddr, value))
+    }
+
+    /// Updates the tracked values with the effects of `instr`.
+    pub fn update(&mut self, instr: &DecodedInstruction, direct_page: u16) {
+        let opcode = instr.definition.opcode;
+        let touches_dspaddr = instr.operand_addresses(direct_page).contains(&DSPADDR);
+
+        if touches_dspaddr {
+            self.dspaddr = match (opcode, instr.operand) {
+                (0x8F, DecodedOperand::DpImm { imm, .. }) => Some(imm),
+                (0xC4 | 0xC5 | 0xDA, _) => self.a,
+                (0xD8 | 0xC9, _) => self.x,
+                (0xCB | 0xCC, _) => self.y,
+                (0xAB | 0xAC, _) => self.dspaddr.map(|v| v.wrapping_add(1)),
+                (0x8B | 0x8C, _) => self.dspaddr.map(|v| v.wrapping_sub(1)),
+                // Reads and compares leave DSPADDR alone.
+                (0xE4 | 0xE5 | 0xF8 | 0xE9 | 0xEB | 0xEC | 0x64 | 0x65 | 0x3E | 0x1E, _)
+                | (0x7E | 0x5E | 0x5A | 0xBA, _) => self.dspaddr,
+                _ => None,
+            };
+        }
+
+        match (opcode, instr.operand) {
+            (0xE8, DecodedOperand::Byte(v)) => self.a = Some(v),
+            (0xCD, DecodedOperand::Byte(v)) => self.x = Some(v),
+            (0x8D, DecodedOperand::Byte(v)) => self.y = Some(v),
+            (0x7D, _) => self.a = self.x,
+            (0xDD, _) => self.a = self.y,
+            (0x5D, _) => self.x = self.a,
+            (0xFD, _) => self.y = self.a,
+            (0xBC, _) => self.a = self.a.map(|v| v.wrapping_add(1)),
+            (0x9C, _) => self.a = self.a.map(|v| v.wrapping_sub(1)),
+            (0x3D, _) => self.x = self.x.map(|v| v.wrapping_add(1)),
+            (0x1D, _) => self.x = self.x.map(|v| v.wrapping_sub(1)),
+            (0xFC, _) => self.y = self.y.map(|v| v.wrapping_add(1)),
+            (0xDC, _) => self.y = self.y.map(|v| v.wrapping_sub(1)),
+            _ if preserves_registers(opcode) => {}
+            _ => {
+                self.a = None;
+                self.x = None;
+                self.y = None;
+            }
+        }
+    }
+}
+
+fn describe(dspaddr: u8, value: Option<u8>) -> String {
+    let name = register_name(dspaddr);
+    match value {
+        Some(value) => format!("DSP {} = #${:02x}", name, value),
+        None => format!("DSP {}", name),
+    }
+}
+
+/// Instructions known to leave A, X and Y unchanged.
+fn preserves_registers(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        // stores
+        0xC4 | 0xC5 | 0xC6 | 0xC7 | 0xC9 | 0xCB | 0xCC | 0xD4 | 0xD5 | 0xD6 | 0xD7 | 0xD8
+            | 0xD9 | 0xDB | 0x8F | 0xFA | 0xDA
+            // read-modify-write on memory
+            | 0xAB | 0xAC | 0xBB | 0x8B | 0x8C | 0x9B | 0x0B | 0x0C | 0x1B | 0x2B | 0x2C
+            | 0x3B | 0x4B | 0x4C | 0x5B | 0x6B | 0x6C | 0x7B | 0x3A | 0x1A | 0x0E | 0x4E
+            // memory-to-memory arithmetic
+            | 0x09 | 0x18 | 0x29 | 0x38 | 0x49 | 0x58 | 0x89 | 0x98 | 0xA9 | 0xB8
+            // compares
+            | 0x64 | 0x65 | 0x66 | 0x67 | 0x68 | 0x69 | 0x74 | 0x75 | 0x76 | 0x77 | 0x78
+            | 0x79 | 0x1E | 0x3E | 0x5E | 0x7E | 0xC8 | 0xAD | 0x5A
+            // flags, stack pushes and bit operations
+            | 0x00 | 0x20 | 0x40 | 0x60 | 0x80 | 0xA0 | 0xC0 | 0xE0 | 0xED
+            | 0x0D | 0x2D | 0x4D | 0x6D
+            | 0x0A | 0x2A | 0x4A | 0x6A | 0x8A | 0xAA | 0xCA | 0xEA
+    ) || opcode & 0x0F == 0x02
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::decode_range;
+    use crate::memory::SliceMemory;
+
+    /// The annotation of each instruction of `code`, tracked from an empty
+    /// state with the direct page at `direct_page`.
+    fn annotations(code: &[u8], direct_page: u16) -> Vec<Option<String>> {
+        let mem = SliceMemory::new(code, 0x0200);
+        let mut tracker = DspTracker::default();
+        decode_range(&mem, 0x0200, 0x0200 + code.len() as u16)
+            .map(|instr| {
+                let instr = instr.unwrap();
+                let note = tracker.annotate(&instr, direct_page);
+                tracker.update(&instr, direct_page);
+                note
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_registers_and_read_only_mirrors() {
+        assert_eq!(register_name(0x00), "V0VOLL");
+        assert_eq!(register_name(0x79), "V7OUTX");
+        assert_eq!(register_name(0x4C), "KON");
+        assert_eq!(register_name(0x7D), "EDL");
+        assert_eq!(register_name(0x7F), "FIR7");
+        assert_eq!(register_name(0x1D), "$1d (unused)");
+        assert_eq!(register_name(0xCC), "KON (read-only mirror)");
+    }
+
+    #[test]
+    fn names_dspdata_writes_after_dspaddr_is_set() {
+        let notes = annotations(
+            &[
+                0x8F, 0x4C, 0xF2, // mov $f2,#$4c
+                0xE8, 0x01, // mov a,#$01
+                0xC4, 0xF3, // mov $f3,a
+                0xAB, 0xF2, // inc $f2
+                0x8F, 0xFF, 0xF3, // mov $f3,#$ff
+                0xE8, 0x6C, // mov a,#$6c
+                0x8D, 0x20, // mov y,#$20
+                0xDA, 0xF2, // movw $f2,ya
+                0xE4, 0xF3, // mov a,$f3
+            ],
+            0,
+        );
+        assert_eq!(
+            notes.into_iter().flatten().collect::<Vec<_>>(),
+            [
+                "DSP KON = #$01",
+                "DSP EON = #$ff",
+                "DSP FLG = #$20",
+                "DSP FLG",
+            ]
+        );
+    }
+
+    #[test]
+    fn forgets_dspaddr_at_block_starts_and_on_page_one() {
+        let mem = SliceMemory::new(&[0x8F, 0x4C, 0xF2, 0xC4, 0xF3], 0x0200);
+        let set = decode_range(&mem, 0x0200, 0x0203).next().unwrap().unwrap();
+        let write = decode_range(&mem, 0x0203, 0x0205).next().unwrap().unwrap();
+        let mut tracker = DspTracker::default();
+        tracker.update(&set, 0);
+        assert_eq!(tracker.annotate(&write, 0).unwrap(), "DSP KON");
+        tracker.reset();
+        assert_eq!(tracker.annotate(&write, 0), None);
+
+        // After setp, $f2 and $f3 are $01f2 and $01f3.
+        let notes = annotations(&[0x8F, 0x4C, 0xF2, 0xC4, 0xF3], 0x0100);
+        assert_eq!(notes, [None, None]);
+        tracker.update(&set, 0x0100);
+        assert_eq!(tracker.annotate(&write, 0), None);
+    }
+}
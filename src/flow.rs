@@ -0,0 +1,423 @@
+use crate::data::{DataKind, DataRegion};
+use crate::decoder;
+use crate::instruction::{DecodedInstruction, DecodedOperand, FlowKind};
+use crate::spc::Spc;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    Branch,
+    Call,
+}
+
+/// Instructions looked back over when inferring a jump table's length.
+const JUMP_TABLE_HISTORY: usize = 16;
+
+/// Bytes reached by following control flow from a set of entry points.
+pub struct CodeMap {
+    starts: Vec<bool>,
+    jump_tables: Vec<DataRegion>,
+}
+
+impl CodeMap {
+    fn new() -> Self {
+        CodeMap {
+            starts: vec![false; 0x10000],
+            jump_tables: Vec::new(),
+        }
+    }
+
+    /// True if an instruction begins at `addr`.
+    pub fn is_code_start(&self, addr: u16) -> bool {
+        self.starts[addr as usize]
+    }
+
+    /// Pointer tables dispatched through by `jmp ($xxxx+x)`.
+    pub fn jump_tables(&self) -> &[DataRegion] {
+        &self.jump_tables
+    }
+
+    fn mark(&mut self, instr: &DecodedInstruction) {
+        self.starts[instr.address as usize] = true;
+    }
+
+    fn in_jump_table(&self, addr: u16) -> bool {
+        self.jump_tables.iter().any(|table| table.contains(addr))
+    }
+}
+
+/// Destination of a `jmp`/`call`/`pcall`/`tcall`/`brk`, read through the
+/// vector table where needed.
+pub fn call_target(instr: &DecodedInstruction, spc: &Spc) -> Option<u16> {
+    match instr.operand {
+        DecodedOperand::Absolute(addr) if matches!(instr.definition.opcode, 0x3F | 0x5F) => {
+            Some(addr)
+        }
+        DecodedOperand::PCall(addr) => Some(0xFF00 | addr as u16),
+        DecodedOperand::TCall(n) => Some(spc.read_word(0xFFDE - (n as u16) * 2)),
+        _ if instr.definition.opcode == 0x0F => Some(spc.read_word(0xFFDE)),
+        _ => None,
+    }
+}
+
+/// Follows jumps, calls and branches from `entries`, marking every
+/// instruction that can be reached. Declared data regions are never
+/// decoded, and a path ends at an instruction that would run into one.
+/// Jump tables found by [`jump_table`] count as data regions from then on,
+/// and their entries are followed too. A branch or jump target carries the
+/// instructions that led to it, so a table behind a taken `bcc` is found.
+pub fn trace(spc: &Spc, entries: &[u16]) -> CodeMap {
+    let mut map = CodeMap::new();
+    let mut pending: Vec<(u16, Vec<DecodedInstruction>)> =
+        entries.iter().map(|&entry| (entry, Vec::new())).collect();
+
+    let is_data = |map: &CodeMap, addr: u16| spc.data.contains(addr) || map.in_jump_table(addr);
+
+    while let Some((mut pc, mut history)) = pending.pop() {
+        while !map.is_code_start(pc) && !is_data(&map, pc) {
+            let Ok(instr) = decoder::decode_one(spc, pc) else {
+                break;
+            };
+            let len = instr.definition.len as u16;
+            if (1..len).any(|i| is_data(&map, pc.wrapping_add(i))) {
+                break;
+            }
+            map.mark(&instr);
+
+            if let Some(table) = jump_table(&instr, &history, spc)
+                && !map.jump_tables.contains(&table)
+            {
+                map.jump_tables.push(table);
+                for i in (0..table.end.wrapping_sub(table.start)).step_by(2) {
+                    pending.push((spc.read_word(table.start.wrapping_add(i)), Vec::new()));
+                }
+            }
+            if history.len() == JUMP_TABLE_HISTORY {
+                history.remove(0);
+            }
+            history.push(instr);
+
+            let flow = instr.flow();
+            if let Some(target) = instr.branch_target() {
+                pending.push((target, history.clone()));
+            }
+            if let Some(target) = call_target(&instr, spc) {
+                let history = match flow {
+                    FlowKind::Jump => history.clone(),
+                    _ => Vec::new(),
+                };
+                pending.push((target, history));
+            }
+            match flow {
+                FlowKind::Sequential | FlowKind::Branch | FlowKind::Call => {
+                    pc = instr.next_address();
+                }
+                FlowKind::Jump | FlowKind::IndirectJump | FlowKind::Return | FlowKind::Halt => {
+                    break;
+                }
+            }
+        }
+    }
+
+    map
+}
+
+/// The table read by a `jmp ($xxxx+x)`. A `ptr` data region at the table
+/// base gives its extent; otherwise the number of entries is inferred from
+/// the bounds check on the index in `history`, the instructions executed
+/// just before: an `and` mask, or a `cmp` whose `bcs` skips the jump for
+/// indexes out of range (or whose `bcc` leads to it).
+pub fn jump_table(
+    instr: &DecodedInstruction,
+    history: &[DecodedInstruction],
+    spc: &Spc,
+) -> Option<DataRegion> {
+    let DecodedOperand::AbsoluteX(base) = instr.operand else {
+        return None;
+    };
+    if instr.definition.opcode != 0x1F {
+        return None;
+    }
+    if let Some(region) = spc.data.region_at(base)
+        && region.start == base
+        && region.kind == DataKind::Pointers
+    {
+        return Some(*region);
+    }
+    let entries = infer_table_entries(instr, history)?;
+    Some(DataRegion::jump_table(base, entries))
+}
+
+/// Walks back from `jmp` to the bounds check on its index, following it
+/// through `mov x,a` and any `asl a` doublings.
+fn infer_table_entries(jmp: &DecodedInstruction, history: &[DecodedInstruction]) -> Option<u16> {
+    #[derive(PartialEq)]
+    enum Reg {
+        A,
+        X,
+    }
+    let mut reg = Reg::X;
+    // The final X is the index register's value here times `scale`.
+    let mut scale: u32 = 1;
+    // The instruction executed after `history[i]`.
+    let after = |i: usize| history.get(i + 1).unwrap_or(jmp);
+    for (i, instr) in history.iter().enumerate().rev() {
+        let opcode = instr.definition.opcode;
+        let max_index = match (opcode, instr.operand, &reg) {
+            (0x5D, _, Reg::X) => {
+                reg = Reg::A;
+                continue;
+            }
+            (0x1C, _, Reg::A) => {
+                scale *= 2;
+                continue;
+            }
+            // cmp a,#n / cmp x,#n followed by a branch out of range.
+            (0x68, DecodedOperand::Byte(n), Reg::A) | (0xC8, DecodedOperand::Byte(n), Reg::X) => {
+                let guard = history.get(i + 1)?;
+                if !passes_below(guard, after(i + 1)) {
+                    return None;
+                }
+                (n as u32).checked_sub(1)?
+            }
+            (0x28, DecodedOperand::Byte(mask), Reg::A) => mask as u32,
+            _ if instr.flow() == FlowKind::Call => return None,
+            _ if writes_register(opcode, reg == Reg::A) => return None,
+            _ => continue,
+        };
+        let entries = max_index * scale / 2 + 1;
+        return u16::try_from(entries).ok().filter(|&n| n <= 0x80);
+    }
+    None
+}
+
+/// True if `branch`, just after a `cmp`, only lets indexes below the
+/// compared value through to `next`: a `bcs` that was not taken or a `bcc`
+/// that was.
+fn passes_below(branch: &DecodedInstruction, next: &DecodedInstruction) -> bool {
+    let taken = next.address != branch.next_address();
+    match branch.definition.opcode {
+        0xB0 => !taken,
+        0x90 => taken && branch.branch_target() == Some(next.address),
+        _ => false,
+    }
+}
+
+/// True if `opcode` changes A (or X when `a` is false).
+fn writes_register(opcode: u8, a: bool) -> bool {
+    if a {
+        matches!(
+            opcode,
+            0xE8 | 0xE6
+                | 0xBF
+                | 0xE4
+                | 0xF4
+                | 0xE5
+                | 0xF5
+                | 0xF6
+                | 0xE7
+                | 0xF7
+                | 0x7D
+                | 0xDD
+                | 0xAE
+                | 0x1C
+                | 0x3C
+                | 0x5C
+                | 0x7C
+                | 0x9C
+                | 0xBC
+                | 0x9F
+                | 0xCF
+                | 0x9E
+                | 0xDF
+                | 0xBE
+                | 0xBA
+                | 0x7A
+                | 0x9A
+        ) || matches!(opcode & 0x1F, 0x04..=0x08 | 0x14..=0x17)
+            && matches!(opcode & 0xE0, 0x00 | 0x20 | 0x40 | 0x80 | 0xA0)
+    } else {
+        matches!(
+            opcode,
+            0xCD | 0xF8 | 0xF9 | 0xE9 | 0x5D | 0x9D | 0xCE | 0x1D | 0x3D | 0xAF | 0xBF
+        )
+    }
+}
+
+/// Every branch, jump and call destination named by `instrs`. An address
+/// that is both branched to and called counts as a call target.
+pub fn targets(instrs: &[DecodedInstruction], spc: &Spc) -> BTreeMap<u16, TargetKind> {
+    let mut targets = BTreeMap::new();
+    for instr in instrs {
+        if let Some(target) = instr.branch_target() {
+            targets.entry(target).or_insert(TargetKind::Branch);
+        }
+        if let Some(target) = call_target(instr, spc) {
+            if instr.flow() == FlowKind::Call {
+                targets.insert(target, TargetKind::Call);
+            } else {
+                targets.entry(target).or_insert(TargetKind::Branch);
+            }
+        }
+    }
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spc::Config;
+
+    fn spc(chunks: &[(u16, &[u8])]) -> Spc {
+        let mut spc = Spc::new(Config::default(), 0x0200, 0x0200);
+        for &(addr, bytes) in chunks {
+            spc.load_rom(bytes, addr);
+        }
+        spc
+    }
+
+    #[test]
+    fn follows_branches_calls_and_vectors() {
+        let spc = spc(&[
+            (
+                0x0200,
+                &[
+                    0xF0, 0x05, // beq $0207
+                    0x3F, 0x20, 0x02, // call $0220
+                    0x2F, 0x10, // bra $0217
+                    0x01, // tcall 0
+                    0x4F, 0x40, // pcall $40
+                    0x5F, 0x30, 0x02, // jmp $0230
+                    0xE8, 0x00, // (after the jmp)
+                ],
+            ),
+            (0x0217, &[0xFF, 0xE8, 0x00]), // stop
+            (0x0220, &[0x6F, 0xE8, 0x00]), // ret
+            (0x0230, &[0xEF, 0xE8, 0x00]), // sleep
+            (0x0240, &[0x6F, 0xE8, 0x00]), // ret
+            (0xFF40, &[0x6F, 0xE8, 0x00]), // ret
+            (0xFFDE, &[0x40, 0x02]),       // tcall 0 vector
+        ]);
+        let map = trace(&spc, &[0x0200]);
+        let starts: Vec<u16> = (0..=0xFFFF).filter(|&a| map.is_code_start(a)).collect();
+        assert_eq!(
+            starts,
+            [
+                0x0200, 0x0202, 0x0205, 0x0207, 0x0208, 0x020A, 0x0217, 0x0220, 0x0230, 0x0240,
+                0xFF40
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_halts_and_data() {
+        // Every SPC700 opcode decodes, so a path only ends at control flow
+        // or a declared data region.
+        let mut spc = spc(&[
+            (0x0200, &[0x00, 0xEF, 0x00]),
+            (0x0300, &[0x00, 0x00, 0x00]),
+            (0x0400, &[0x00, 0xE8, 0x12]), // nop, mov a,#$12 over the region
+        ]);
+        spc.data.insert(DataRegion {
+            start: 0x0301,
+            end: 0x0303,
+            kind: DataKind::Bytes,
+        });
+        spc.data.insert(DataRegion {
+            start: 0x0402,
+            end: 0x0404,
+            kind: DataKind::Words,
+        });
+        let map = trace(&spc, &[0x0200, 0x0300, 0x0400]);
+        assert!(map.is_code_start(0x0201));
+        assert!(!map.is_code_start(0x0202));
+        assert!(map.is_code_start(0x0300));
+        assert!(!map.is_code_start(0x0301));
+        assert!(map.is_code_start(0x0400));
+        assert!(!map.is_code_start(0x0401));
+    }
+
+    #[test]
+    fn infers_tables_behind_a_bcs_guard() {
+        let table_code = |guard: u8| {
+            spc(&[
+                (
+                    0x0200,
+                    &[
+                        0x68, 0x03, // cmp a,#$03
+                        guard, 0x05, // bcs/bne/bcc $0209
+                        0x1C, // asl a
+                        0x5D, // mov x,a
+                        0x1F, 0x0B, 0x02, // jmp ($020b+x)
+                        0x00, // nop
+                        0x00, // nop, running into the table
+                        0x00, 0x03, 0x10, 0x03, 0x20, 0x03, // the table
+                    ],
+                ),
+                (0x0300, &[0x6F]),
+                (0x0310, &[0x6F]),
+                (0x0320, &[0x6F]),
+            ])
+        };
+
+        let map = trace(&table_code(0xB0), &[0x0200]);
+        assert_eq!(map.jump_tables(), [DataRegion::jump_table(0x020B, 3)]);
+        for addr in [0x0209, 0x020A, 0x0300, 0x0310, 0x0320] {
+            assert!(map.is_code_start(addr), "{:04x}", addr);
+        }
+        assert!(!map.is_code_start(0x020B));
+
+        // bne does not bound the index, and a bcc that falls through to the
+        // jump lets only indexes past the end through.
+        for guard in [0xD0, 0x90] {
+            let map = trace(&table_code(guard), &[0x0200]);
+            assert!(map.jump_tables().is_empty(), "guard {:02x}", guard);
+            assert!(!map.is_code_start(0x0300));
+        }
+    }
+
+    #[test]
+    fn infers_tables_behind_a_taken_bcc_guard() {
+        let spc = spc(&[(
+            0x0200,
+            &[
+                0xC8, 0x04, // cmp x,#$04
+                0x90, 0x01, // bcc $0205
+                0x6F, // ret
+                0x1F, 0x00, 0x03, // jmp ($0300+x)
+            ],
+        )]);
+        let decode = |addr| decoder::decode_one(&spc, addr).unwrap();
+        let history = [decode(0x0200), decode(0x0202)];
+        assert_eq!(
+            jump_table(&decode(0x0205), &history, &spc),
+            Some(DataRegion::jump_table(0x0300, 2))
+        );
+        // Without the check there is nothing to size the table by.
+        assert_eq!(jump_table(&decode(0x0205), &history[1..], &spc), None);
+    }
+
+    #[test]
+    fn traces_tables_behind_a_taken_bcc_guard() {
+        let spc = spc(&[
+            (
+                0x0200,
+                &[
+                    0x68, 0x02, // cmp a,#$02
+                    0x90, 0x01, // bcc $0205
+                    0x6F, // ret
+                    0x1C, // asl a
+                    0x5D, // mov x,a
+                    0x1F, 0x0A, 0x02, // jmp ($020a+x)
+                    0x00, 0x03, 0x10, 0x03, // the table
+                ],
+            ),
+            (0x0300, &[0x6F]),
+            (0x0310, &[0x6F]),
+        ]);
+        let map = trace(&spc, &[0x0200]);
+        assert_eq!(map.jump_tables(), [DataRegion::jump_table(0x020A, 2)]);
+        assert!(map.is_code_start(0x0300));
+        assert!(map.is_code_start(0x0310));
+    }
+}
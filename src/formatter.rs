@@ -1,13 +1,164 @@
-use crate::instruction::{DecodedInstruction, DecodedOperand};
-use crate::spc::{Config, Spc};
+use crate::cpu::BRANCH_TAKEN_CYCLES;
+use crate::data::DataKind;
+use crate::flow;
+use crate::hwregs;
+use crate::instruction::{DecodedInstruction, DecodedOperand, FlowKind};
+use crate::spc::Spc;
+use crate::syntax::BitStyle;
+
+/// Column the `;` of end-of-line comments is aligned to.
+pub const COMMENT_COLUMN: usize = 24;
 
 pub fn format_instruction(instr: &DecodedInstruction, spc: &Spc) -> String {
     let prefix = format_prefix(instr, spc);
-    let disassembly = format_disassembly(instr, &spc.config);
-    format!("{}{}", prefix, disassembly).trim_end().to_string()
+    let disassembly = format_disassembly(instr, spc);
+    let comments = format_comments(instr, spc);
+    if comments.is_empty() {
+        format!("{}{}", prefix, disassembly).trim_end().to_string()
+    } else {
+        format!(
+            "{}{:<width$} ; {}",
+            prefix,
+            disassembly,
+            comments.join("; "),
+            width = COMMENT_COLUMN - 1
+        )
+    }
+}
+
+/// The address, hex and disassembly columns of `format_instruction`,
+/// without the trailing comments.
+pub fn format_code(instr: &DecodedInstruction, spc: &Spc) -> String {
+    format!(
+        "{}{}",
+        format_prefix(instr, spc),
+        format_disassembly(instr, spc)
+    )
+}
+
+/// The annotations `format_instruction` appends after `;`.
+pub fn format_comments(instr: &DecodedInstruction, spc: &Spc) -> Vec<String> {
+    let mut comments = Vec::new();
+    if let Some(comment) = spc.comments.inline(instr.address) {
+        comments.push(comment.to_string());
+    }
+    if let DecodedOperand::TCall(_) | DecodedOperand::PCall(_) = instr.operand
+        && let Some(target) = flow::call_target(instr, spc)
+    {
+        comments.push(match symbol_name(target, spc) {
+            Some(name) => format!("-> {} (${:04x})", name, target),
+            None => format!("-> ${:04x}", target),
+        });
+    }
+    if spc.config.hw_regs {
+        for addr in instr.operand_addresses(spc.direct_page) {
+            if let Some(reg) = hwregs::lookup(addr) {
+                comments.push(format!("{}: {}", reg.name, reg.description));
+            }
+        }
+    }
+    if spc.config.dsp_regs
+        && let Some(comment) = spc.dsp_tracker.annotate(instr, spc.direct_page)
+    {
+        comments.push(comment);
+    }
+    comments
 }
 
-fn format_prefix(instr: &DecodedInstruction, spc: &Spc) -> String {
+/// Formats `len` bytes at `addr` as a data directive of the given kind,
+/// leaving the hex column blank so it lines up with the instructions
+/// around it. An end-of-line comment at `addr` is appended.
+pub fn format_data(addr: u16, len: u16, kind: DataKind, spc: &Spc) -> String {
+    let line = format!(
+        "{}{}",
+        format_data_prefix(addr, spc),
+        format_data_directive(addr, len, kind, spc)
+    );
+    match spc.comments.inline(addr) {
+        Some(comment) => format!("{} ; {}", line, comment),
+        None => line,
+    }
+}
+
+/// The address column of a data line, followed by blank hex and cycle
+/// columns.
+pub fn format_data_prefix(addr: u16, spc: &Spc) -> String {
+    let mut prefix = String::new();
+    let config = &spc.config;
+    if config.show_addr {
+        prefix.push_str(&format!("{:04x}: ", addr));
+    }
+    if config.show_hex {
+        prefix.push_str(&format!("{:<10}", ""));
+    }
+    if config.show_cycles {
+        prefix.push_str(&format!("{:<11}", ""));
+    }
+    prefix
+}
+
+/// The directive part of `format_data`, such as `dw    $1234,$5678`.
+pub fn format_data_directive(addr: u16, len: u16, kind: DataKind, spc: &Spc) -> String {
+    let (directive, items) = format_data_items(addr, len, kind, spc);
+    format!("{:<5} {}", directive, items.join(","))
+}
+
+/// The directive of a data line and its comma-separated items.
+pub fn format_data_items(
+    addr: u16,
+    len: u16,
+    kind: DataKind,
+    spc: &Spc,
+) -> (&'static str, Vec<String>) {
+    let syntax = spc.config.dialect.syntax();
+    let byte = |i: u16| spc.read_byte(addr.wrapping_add(i));
+    let word = |i: u16| spc.read_word(addr.wrapping_add(i));
+    match kind {
+        DataKind::Bytes => (
+            syntax.byte_directive,
+            (0..len).map(|i| format!("${:02x}", byte(i))).collect(),
+        ),
+        DataKind::Words => (
+            syntax.word_directive,
+            (0..len)
+                .step_by(2)
+                .map(|i| format!("${:04x}", word(i)))
+                .collect(),
+        ),
+        DataKind::Pointers => (
+            syntax.word_directive,
+            (0..len)
+                .step_by(2)
+                .map(|i| format_abs(word(i), spc))
+                .collect(),
+        ),
+        DataKind::Text => (syntax.byte_directive, format_text((0..len).map(byte))),
+    }
+}
+
+/// Splits bytes into quoted runs of printable ASCII and `$xx` items.
+fn format_text(bytes: impl Iterator<Item = u8>) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut text = String::new();
+    for byte in bytes {
+        if (0x20..0x7F).contains(&byte) && byte != b'"' {
+            text.push(byte as char);
+            continue;
+        }
+        if !text.is_empty() {
+            items.push(format!("\"{}\"", text));
+            text.clear();
+        }
+        items.push(format!("${:02x}", byte));
+    }
+    if !text.is_empty() {
+        items.push(format!("\"{}\"", text));
+    }
+    items
+}
+
+/// The address, hex and cycle columns in front of an instruction.
+pub fn format_prefix(instr: &DecodedInstruction, spc: &Spc) -> String {
     let mut prefix = String::new();
     let config = &spc.config;
     if config.show_addr {
@@ -22,226 +173,482 @@ fn format_prefix(instr: &DecodedInstruction, spc: &Spc) -> String {
         }
         prefix.push_str(&format!("{:<10}", hex_part));
     }
+    if config.show_cycles {
+        prefix.push_str(&format!(
+            "{:<4}{:<7}",
+            format_cycles(instr),
+            format!("[{}]", spc.block_cycles)
+        ));
+    }
     prefix
 }
 
-fn format_disassembly(instr: &DecodedInstruction, config: &Config) -> String {
-    let mne = instr.definition.mnemonic;
+/// Base cycle count, with the taken count after a slash for conditional
+/// branches: `2/4`.
+pub fn format_cycles(instr: &DecodedInstruction) -> String {
+    let cycles = instr.definition.cycles;
+    if instr.flow() == FlowKind::Branch {
+        format!("{}/{}", cycles, cycles + BRANCH_TAKEN_CYCLES)
+    } else {
+        cycles.to_string()
+    }
+}
+
+/// The mnemonic and operands of `instr`, such as `mov   a,$04+x`.
+pub fn format_disassembly(instr: &DecodedInstruction, spc: &Spc) -> String {
+    let syntax = spc.config.dialect.syntax();
+    let abs = |addr: u16| format!("{}{}", syntax.abs_prefix, format_abs(addr, spc));
+    let dp = |addr: u8| format_dp(addr, spc);
+    // Memory indirection: `($12+x)`, or `[$12+x]` in Sony's notation.
+    let indirect = |inner: String| {
+        if syntax.brackets {
+            format!("[{}]", inner)
+        } else {
+            format!("({})", inner)
+        }
+    };
+    let mut mne = instr.definition.mnemonic;
     let opcode = instr.definition.opcode;
-    let len = instr.definition.len;
-    let pc = instr.address;
+    // `set3 $12` is `set1 $12.3` where the bit is written as an operand.
+    let bit = opcode >> 5;
+    let dotted_bits = syntax.bit_style == BitStyle::Dotted;
+    let bit_operand = |addr: u8| {
+        if dotted_bits {
+            format!("{}.{}", dp(addr), bit)
+        } else {
+            dp(addr)
+        }
+    };
+
+    // Register names in the dialect's case, so that labels keep theirs.
+    let [a, x, y, ya, sp, psw, c] = ["a", "x", "y", "ya", "sp", "psw", "c"].map(|name| {
+        if syntax.uppercase {
+            name.to_ascii_uppercase()
+        } else {
+            name.to_string()
+        }
+    });
 
     let operand_str = match &instr.operand {
         DecodedOperand::None => match opcode {
-            0x0D => "psw".to_string(),
-            0x2D => "a".to_string(),
-            0x4D => "x".to_string(),
-            0x6D => "y".to_string(),
-            0x8E => "psw".to_string(),
-            0xAE => "a".to_string(),
-            0xCE => "x".to_string(),
-            0xEE => "y".to_string(),
-            0x1C | 0x3C | 0x5C | 0x7C | 0x9C | 0xBC => "a".to_string(),
-            0x1D => "x".to_string(),
-            0x3D => "x".to_string(),
-            0xDC => "y".to_string(),
-            0xFC => "y".to_string(),
-            0x5D => "x,a".to_string(),
-            0x7D => "a,x".to_string(),
-            0x9D => "x,sp".to_string(),
-            0xBD => "sp,x".to_string(),
-            0xDD => "a,y".to_string(),
-            0xFD => "y,a".to_string(),
-            0xBE => "a".to_string(),
-            0xDF => "a".to_string(),
-            0x9F => "a".to_string(),
-            0xCF => "ya".to_string(),
-            0x9E => "ya,x".to_string(),
-            0x19 | 0x39 | 0x59 | 0x79 | 0x99 | 0xB9 => "(x),(y)".to_string(),
+            0x0D => psw.clone(),
+            0x2D => a.clone(),
+            0x4D => x.clone(),
+            0x6D => y.clone(),
+            0x8E => psw.clone(),
+            0xAE => a.clone(),
+            0xCE => x.clone(),
+            0xEE => y.clone(),
+            0x1C | 0x3C | 0x5C | 0x7C | 0x9C | 0xBC => a.clone(),
+            0x1D => x.clone(),
+            0x3D => x.clone(),
+            0xDC => y.clone(),
+            0xFC => y.clone(),
+            0x5D => format!("{x},{a}"),
+            0x7D => format!("{a},{x}"),
+            0x9D => format!("{x},{sp}"),
+            0xBD => format!("{sp},{x}"),
+            0xDD => format!("{a},{y}"),
+            0xFD => format!("{y},{a}"),
+            0xBE => a.clone(),
+            0xDF => a.clone(),
+            0x9F => a.clone(),
+            0xCF => ya.clone(),
+            0x9E => format!("{ya},{x}"),
+            0x19 | 0x39 | 0x59 | 0x79 | 0x99 | 0xB9 => format!("({x}),({y})"),
             _ => "".to_string(),
         },
         DecodedOperand::TCall(n) => format!("{}", n),
         DecodedOperand::PCall(addr) => format!("${:02x}", addr),
         DecodedOperand::Byte(val) => match opcode {
-            0xC8 | 0xCD => format!("x,#${:02x}", val),
-            0x8D | 0xAD => format!("y,#${:02x}", val),
-            _ => format!("a,#${:02x}", val),
+            0xC8 | 0xCD => format!("{x},#${:02x}", val),
+            0x8D | 0xAD => format!("{y},#${:02x}", val),
+            _ => format!("{a},#${:02x}", val),
         },
         DecodedOperand::Relative(offset) => {
-            let target_str = if config.resolve_rel {
-                let target = pc
-                    .wrapping_add(len as u16)
-                    .wrapping_add(*offset as i16 as u16);
-                format!("${:04x}", target)
-            } else {
-                format!("${:02x}", *offset as u8)
-            };
+            let target_str = format_branch(instr, *offset, spc);
             if opcode == 0xFE {
-                format!("y,{}", target_str)
+                format!("{y},{}", target_str)
             } else {
                 target_str
             }
         }
         DecodedOperand::Direct(addr) => match opcode {
-            0x1A | 0x3A => format!("${:02x}", addr),
-            0x5A | 0x7A | 0x9A | 0xBA => format!("ya,${:02x}", addr),
-            0xDA => format!("${:02x},ya", addr),
+            0x1A | 0x3A => dp(*addr),
+            0x5A | 0x7A | 0x9A | 0xBA => format!("{ya},{}", dp(*addr)),
+            0xDA => format!("{},{ya}", dp(*addr)),
 
-            0x3E | 0xF8 => format!("x,${:02x}", addr),
+            0x3E | 0xF8 => format!("{x},{}", dp(*addr)),
 
-            0x7E | 0xEB => format!("y,${:02x}", addr),
+            0x7E | 0xEB => format!("{y},{}", dp(*addr)),
 
-            0xC4 => format!("${:02x},a", addr),
-            0xCB => format!("${:02x},y", addr),
-            0xD8 => format!("${:02x},x", addr),
+            0xC4 => format!("{},{a}", dp(*addr)),
+            0xCB => format!("{},{y}", dp(*addr)),
+            0xD8 => format!("{},{x}", dp(*addr)),
 
-            0x04 | 0x24 | 0x44 | 0x64 | 0x84 | 0xA4 | 0xE4 => format!("a,${:02x}", addr),
+            0x04 | 0x24 | 0x44 | 0x64 | 0x84 | 0xA4 | 0xE4 => format!("{a},{}", dp(*addr)),
 
-            _ => format!("${:02x}", addr),
+            _ => dp(*addr),
         },
         DecodedOperand::Absolute(addr) => match mne {
-            "jmp" | "call" => format!("${:04x}", addr),
+            "jmp" | "call" => abs(*addr),
 
             "mov" => match opcode {
-                0xC5 => format!("${:04x},a", addr),
-                0xC9 => format!("${:04x},x", addr),
-                0xCC => format!("${:04x},y", addr),
-                0xE5 => format!("a,${:04x}", addr),
-                0xE9 => format!("x,${:04x}", addr),
-                0xEC => format!("y,${:04x}", addr),
-                _ => format!("${:04x}", addr),
+                0xC5 => format!("{},{a}", abs(*addr)),
+                0xC9 => format!("{},{x}", abs(*addr)),
+                0xCC => format!("{},{y}", abs(*addr)),
+                0xE5 => format!("{a},{}", abs(*addr)),
+                0xE9 => format!("{x},{}", abs(*addr)),
+                0xEC => format!("{y},{}", abs(*addr)),
+                _ => abs(*addr),
             },
 
             "cmp" => match opcode {
-                0x1E => format!("x,${:04x}", addr),
-                0x5E => format!("y,${:04x}", addr),
-                _ => format!("a,${:04x}", addr),
+                0x1E => format!("{x},{}", abs(*addr)),
+                0x5E => format!("{y},{}", abs(*addr)),
+                _ => format!("{a},{}", abs(*addr)),
             },
 
-            "asl" | "dec" | "inc" | "lsr" | "rol" | "ror" => format!("${:04x}", addr),
+            "asl" | "dec" | "inc" | "lsr" | "rol" | "ror" => abs(*addr),
 
-            "tset1" | "tclr1" => format!("${:04x}", addr),
+            "tset1" | "tclr1" => abs(*addr),
 
-            _ => format!("a,${:04x}", addr),
+            _ => format!("{a},{}", abs(*addr)),
         },
         DecodedOperand::DirectX(addr) => match mne {
             "mov" => match opcode {
-                0xFB => format!("y,${:02x}+x", addr),
-                0xDB => format!("${:02x}+x,y", addr),
-                0xD4 => format!("${:02x}+x,a", addr),
-                _ => format!("a,${:02x}+x", addr),
+                0xFB => format!("{y},{}+{x}", dp(*addr)),
+                0xDB => format!("{}+{x},{y}", dp(*addr)),
+                0xD4 => format!("{}+{x},{a}", dp(*addr)),
+                _ => format!("{a},{}+{x}", dp(*addr)),
             },
-            "adc" | "and" | "cmp" | "eor" | "or" | "sbc" => format!("a,${:02x}+x", addr),
-            _ => format!("${:02x}+x", addr),
+            "adc" | "and" | "cmp" | "eor" | "or" | "sbc" => format!("{a},{}+{x}", dp(*addr)),
+            _ => format!("{}+{x}", dp(*addr)),
         },
         DecodedOperand::DirectY(addr) => match mne {
             "mov" => match opcode {
-                0xD9 => format!("${:02x}+y,x", addr),
-                _ => format!("x,${:02x}+y", addr),
+                0xD9 => format!("{}+{y},{x}", dp(*addr)),
+                _ => format!("{x},{}+{y}", dp(*addr)),
             },
-            _ => format!("a,${:02x}+y", addr),
+            _ => format!("{a},{}+{y}", dp(*addr)),
         },
         DecodedOperand::AbsoluteX(addr) => match mne {
-            "jmp" => format!("(${:04x}+x)", addr),
+            "jmp" => indirect(format!("{}+{x}", abs(*addr))),
             "mov" => match opcode {
-                0xD5 => format!("${:04x}+x,a", addr),
-                _ => format!("a,${:04x}+x", addr),
+                0xD5 => format!("{}+{x},{a}", abs(*addr)),
+                _ => format!("{a},{}+{x}", abs(*addr)),
             },
-            _ => format!("a,${:04x}+x", addr),
+            _ => format!("{a},{}+{x}", abs(*addr)),
         },
         DecodedOperand::AbsoluteY(addr) => match mne {
             "mov" => match opcode {
-                0xD6 => format!("${:04x}+y,a", addr),
-                _ => format!("a,${:04x}+y", addr),
-            },
-            _ => format!("a,${:04x}+y", addr),
-        },
-        DecodedOperand::IndirectX(addr) => match mne {
-            "mov" => match opcode {
-                0xC7 => format!("(${:02x}+x),a", addr),
-                _ => format!("a,(${:02x}+x)", addr),
+                0xD6 => format!("{}+{y},{a}", abs(*addr)),
+                _ => format!("{a},{}+{y}", abs(*addr)),
             },
-            _ => format!("a,(${:02x}+x)", addr),
-        },
-        DecodedOperand::IndirectY(addr) => match mne {
-            "mov" => match opcode {
-                0xD7 => format!("(${:02x})+y,a", addr),
-                _ => format!("a,(${:02x})+y", addr),
-            },
-            _ => format!("a,(${:02x})+y", addr),
+            _ => format!("{a},{}+{y}", abs(*addr)),
         },
+        DecodedOperand::IndirectX(addr) => {
+            let operand = indirect(format!("{}+{x}", dp(*addr)));
+            match opcode {
+                0xC7 => format!("{},{a}", operand),
+                _ => format!("{a},{}", operand),
+            }
+        }
+        DecodedOperand::IndirectY(addr) => {
+            let operand = format!("{}+{y}", indirect(dp(*addr)));
+            match opcode {
+                0xD7 => format!("{},{a}", operand),
+                _ => format!("{a},{}", operand),
+            }
+        }
 
         DecodedOperand::Indirect => match opcode {
-            0xC6 => "(x),a".to_string(),
-            _ => "a,(x)".to_string(),
+            0xC6 => format!("({x}),{a}"),
+            _ => format!("{a},({x})"),
         },
         DecodedOperand::IndirectAutoInc => match opcode {
-            0xAF => "(x)+,a".to_string(),
-            0xBF => "a,(x)+".to_string(),
+            0xAF => format!("({x})+,{a}"),
+            0xBF => format!("{a},({x})+"),
             _ => "???".to_string(),
         },
 
-        DecodedOperand::DirectBit { addr } => format!("${:02x}", addr),
+        DecodedOperand::DirectBit { addr } => {
+            if dotted_bits {
+                mne = if opcode & 0x10 != 0 { "clr1" } else { "set1" };
+            }
+            bit_operand(*addr)
+        }
         DecodedOperand::DirectBitRelative { addr, offset } => {
-            let branch = if config.resolve_rel {
-                let target = pc
-                    .wrapping_add(len as u16)
-                    .wrapping_add(*offset as i16 as u16);
-                format!("${:04x}", target)
-            } else {
-                format!("${:02x}", *offset as u8)
-            };
-            format!("${:02x},{}", addr, branch)
+            if dotted_bits {
+                mne = if opcode & 0x10 != 0 { "bbc" } else { "bbs" };
+            }
+            let branch = format_branch(instr, *offset, spc);
+            format!("{},{}", bit_operand(*addr), branch)
         }
         DecodedOperand::MemoryBit { addr, bit } => {
-            let base = format!("${:04x},{}", addr, bit);
+            let base = format!(
+                "{}{}{}",
+                format_abs(*addr, spc),
+                syntax.mem_bit_separator,
+                bit
+            );
             match mne {
                 "or1" | "and1" | "eor1" => {
                     if (opcode & 0x20) != 0 {
-                        format!("c,!({})", base)
+                        if syntax.slash_not {
+                            format!("{c},/{}", base)
+                        } else {
+                            format!("{c},!({})", base)
+                        }
                     } else {
-                        format!("c,{}", base)
+                        format!("{c},{}", base)
                     }
                 }
                 "mov1" => {
                     if opcode == 0xAA {
-                        format!("c,{}", base)
+                        format!("{c},{}", base)
                     } else {
-                        format!("{},c", base)
+                        format!("{},{c}", base)
                     }
                 }
                 "not1" => base,
                 _ => "???".to_string(),
             }
         }
-        DecodedOperand::DpToDp { dest, src } => format!("(${:02x}),(${:02x})", dest, src),
-        DecodedOperand::DpImm { addr, imm } => format!("${:02x},#${:02x}", addr, imm),
-        DecodedOperand::DirectRelative { addr, offset } => {
-            let branch = if config.resolve_rel {
-                let target = pc
-                    .wrapping_add(len as u16)
-                    .wrapping_add(*offset as i16 as u16);
-                format!("${:04x}", target)
+        DecodedOperand::DpToDp { dest, src } => {
+            if syntax.bare_dp_to_dp {
+                format!("{},{}", dp(*dest), dp(*src))
             } else {
-                format!("${:02x}", *offset as u8)
-            };
-            format!("${:02x},{}", addr, branch)
+                format!("({}),({})", dp(*dest), dp(*src))
+            }
+        }
+        DecodedOperand::DpImm { addr, imm } => format!("{},#${:02x}", dp(*addr), imm),
+        DecodedOperand::DirectRelative { addr, offset } => {
+            let branch = format_branch(instr, *offset, spc);
+            format!("{},{}", dp(*addr), branch)
         }
         DecodedOperand::DirectXRelative { addr, offset } => {
-            let branch = if config.resolve_rel {
-                let target = pc
-                    .wrapping_add(len as u16)
-                    .wrapping_add(*offset as i16 as u16);
-                format!("${:04x}", target)
-            } else {
-                format!("${:02x}", *offset as u8)
-            };
-            format!("${:02x}+x,{}", addr, branch)
+            let branch = format_branch(instr, *offset, spc);
+            format!("{}+{x},{}", dp(*addr), branch)
         }
     };
-    if operand_str.is_empty() {
+    let mne = if syntax.uppercase {
+        mne.to_ascii_uppercase()
+    } else {
         mne.to_string()
+    };
+    if operand_str.is_empty() {
+        return mne;
+    }
+    let operand_str = operand_str.replace(',', syntax.operand_separator);
+    format!("{:<5} {}", mne, operand_str)
+}
+
+fn format_branch(instr: &DecodedInstruction, offset: i8, spc: &Spc) -> String {
+    match instr.branch_target() {
+        Some(target) if spc.config.resolve_rel => format_abs(target, spc),
+        _ => format!("${:02x}", offset as u8),
+    }
+}
+
+fn format_dp(addr: u8, spc: &Spc) -> String {
+    match symbol_name(spc.direct_page | addr as u16, spc) {
+        Some(name) => name.to_string(),
+        None => format!("${:02x}", addr),
+    }
+}
+
+/// `addr` as its symbol or label name, or as `$xxxx`.
+pub fn format_abs(addr: u16, spc: &Spc) -> String {
+    match symbol_name(addr, spc) {
+        Some(name) => name.to_string(),
+        None => format!("${:04x}", addr),
+    }
+}
+
+fn symbol_name(addr: u16, spc: &Spc) -> Option<&str> {
+    if let Some(name) = spc.labels.get(addr) {
+        return Some(name);
+    }
+    if spc.config.hw_regs {
+        hwregs::lookup(addr).map(|reg| reg.name)
     } else {
-        format!("{:<5} {}", mne, operand_str)
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DataRegion;
+    use crate::decoder::decode_range;
+    use crate::listing::write_listing;
+    use crate::spc::Config;
+    use crate::syntax::Dialect;
+
+    #[test]
+    fn dialects_write_their_own_operands_and_directives() {
+        let code = [
+            0x62, 0x12, // set3 $12
+            0x63, 0x12, 0xFB, // bbs3 $12,$0800
+            0x6A, 0x34, 0xB2, // and1 c,!($1234,5)
+            0xE5, 0x34, 0x12, // mov a,$1234
+            0x01, 0x02, // data
+        ];
+        let table = [
+            (
+                Dialect::Spcdas,
+                "set3  $12\n\
+                 bbs3  $12,$0800\n\
+                 and1  c,!($1234,5)\n\
+                 mov   a,$1234\n\
+                 db    $01,$02\n",
+            ),
+            (
+                Dialect::Bass,
+                "arch snes.smp\n\
+                 set3  $12\n\
+                 bbs3  $12,$0800\n\
+                 and1  c,!($1234,5)\n\
+                 mov   a,$1234\n\
+                 db    $01,$02\n",
+            ),
+            (
+                Dialect::Wla,
+                "set1  $12.3\n\
+                 bbs   $12.3,$0800\n\
+                 and1  c,/$1234.5\n\
+                 mov   a,!$1234\n\
+                 .db   $01,$02\n",
+            ),
+            (
+                Dialect::Asar,
+                "arch spc700\n\
+                 set1  $12.3\n\
+                 bbs   $12.3,$0800\n\
+                 and1  c,/$1234.5\n\
+                 mov   a,$1234\n\
+                 db    $01,$02\n",
+            ),
+            (
+                Dialect::Ca65,
+                "set3  $12\n\
+                 bbs3  $12,$0800\n\
+                 and1  c,!($1234,5)\n\
+                 mov   a,a:$1234\n\
+                 .byte $01,$02\n",
+            ),
+            (
+                Dialect::Sony,
+                "set1  $12.3\n\
+                 bbs   $12.3,$0800\n\
+                 and1  c,/$1234.5\n\
+                 mov   a,!$1234\n\
+                 db    $01,$02\n",
+            ),
+            (
+                Dialect::Official,
+                "SET1  $12.3\n\
+                 BBS   $12.3, $0800\n\
+                 AND1  C, /$1234.5\n\
+                 MOV   A, !$1234\n\
+                 DB    $01,$02\n",
+            ),
+        ];
+        for (dialect, expected) in table {
+            let config = Config {
+                show_addr: false,
+                show_hex: false,
+                dialect,
+                ..Config::default()
+            };
+            let mut spc = Spc::new(config, 0x0800, 0x080d);
+            spc.load_rom(&code, 0x0800);
+            spc.data.insert(DataRegion {
+                start: 0x080b,
+                end: 0x080d,
+                kind: DataKind::Bytes,
+            });
+            let mut text = Vec::new();
+            write_listing(&mut text, &mut spc, None).unwrap();
+            assert_eq!(String::from_utf8(text).unwrap(), expected, "{}", dialect);
+        }
+    }
+
+    #[test]
+    fn official_syntax_upper_cases_registers_but_not_labels() {
+        let code = [
+            0xF7, 0x30, // mov a,[$30]+y
+            0x1F, 0x34, 0x12, // jmp [!$1234+x]
+            0xFA, 0x31, 0x30, // mov $30,$31
+            0xBA, 0x31, // movw ya,$31
+            0xF5, 0x34, 0x12, // mov a,$1234+x
+        ];
+        let config = Config {
+            dialect: Dialect::Official,
+            ..Config::default()
+        };
+        let mut spc = Spc::new(config, 0x0800, 0x080d);
+        spc.load_rom(&code, 0x0800);
+        spc.labels.insert(0x0030, "x".to_string());
+        spc.labels.insert(0x1234, "a".to_string());
+        let lines: Vec<_> = decode_range(&spc, 0x0800, 0x080d)
+            .map(|instr| format_disassembly(&instr.unwrap(), &spc))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "MOV   A, [x]+Y",
+                "JMP   [!a+X]",
+                "MOV   x, $31",
+                "MOVW  YA, $31",
+                "MOV   A, !a+X",
+            ]
+        );
+    }
+
+    #[test]
+    fn names_io_registers_on_the_zero_page_with_hw_regs() {
+        let code = [
+            0xC4, 0xF4, // mov $f4,a
+            0x40, // setp
+            0xC4, 0xF4, // mov $f4,a
+        ];
+        for hw_regs in [false, true] {
+            let config = Config {
+                hw_regs,
+                ..Config::default()
+            };
+            let mut spc = Spc::new(config, 0x0200, 0x0205);
+            spc.load_rom(&code, 0x0200);
+            let mut text = Vec::new();
+            write_listing(&mut text, &mut spc, None).unwrap();
+            let first = if hw_regs {
+                "0200: c4 f4     mov   CPUIO0,a          ; CPUIO0: S-CPU I/O port 0 ($2140)"
+            } else {
+                "0200: c4 f4     mov   $f4,a"
+            };
+            assert_eq!(
+                String::from_utf8(text).unwrap(),
+                format!(
+                    "{}\n\
+                     0202: 40        setp\n\
+                     0203: c4 f4     mov   $f4,a\n",
+                    first
+                ),
+                "hw_regs {}",
+                hw_regs
+            );
+        }
+    }
+
+    #[test]
+    fn format_text_quotes_printable_runs() {
+        let text = |bytes: &[u8]| format_text(bytes.iter().copied());
+        assert_eq!(text(b"Hi!"), ["\"Hi!\""]);
+        assert_eq!(text(b"A\0B"), ["\"A\"", "$00", "\"B\""]);
+        assert_eq!(text(b"say \"x\""), ["\"say \"", "$22", "\"x\"", "$22"]);
+        assert_eq!(text(&[0x1F, b' ', b'~', 0x7F]), ["$1f", "\" ~\"", "$7f"]);
+        assert!(text(&[]).is_empty());
     }
 }
@@ -0,0 +1,342 @@
+use crate::data::DataKind;
+use crate::decoder::DecodeError;
+use crate::flow::{self, CodeMap};
+use crate::formatter::{self, COMMENT_COLUMN};
+use crate::hwregs;
+use crate::instruction::{DecodedInstruction, DecodedOperand, FlowKind};
+use crate::listing::{self, Item};
+use crate::spc::Spc;
+use std::io::{self, Write};
+use std::ops::Range;
+
+const DSPDATA: u16 = 0x00F3;
+
+const PAGE_START: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>SPC700 listing</title>
+<style>
+body { margin: 1em; background: #fdfdfd; color: #222; }
+pre { font: 13px/1.4 monospace; }
+a { color: #0451a5; text-decoration: none; }
+a:hover { text-decoration: underline; }
+.label { color: #a31515; font-weight: bold; }
+.comment { color: #2a7a2a; }
+.data { background: #f1edfa; color: #553a8a; }
+.unknown { color: #999; }
+.reg { border-bottom: 1px dotted #888; cursor: help; }
+:target { background: #fff3a8; }
+</style>
+</head>
+<body>
+<pre>"#;
+
+const PAGE_END: &str = "</pre>\n</body>\n</html>";
+
+/// Writes the listing as a self-contained HTML page. Each line is an
+/// anchor named after its address, branch, jump and call targets and
+/// pointer table entries in the listing link to it, data regions are
+/// shaded, and I/O and S-DSP register operands explain themselves in a
+/// tooltip.
+pub fn write_html(writer: &mut dyn Write, spc: &mut Spc, code: Option<&CodeMap>) -> io::Result<()> {
+    writeln!(writer, "{}", PAGE_START)?;
+    if let Some(header) = spc.config.dialect.syntax().header {
+        writeln!(writer, "{}", escape(header))?;
+    }
+    listing::walk(spc, code, |item, spc| match item {
+        Item::Instruction(instr) => {
+            let id = write_notes(writer, instr.address, spc)?;
+            writeln!(
+                writer,
+                "<span{}>{}</span>",
+                id,
+                instruction_html(instr, spc)
+            )
+        }
+        Item::Data { addr, len, kind } => {
+            let id = write_notes(writer, *addr, spc)?;
+            writeln!(
+                writer,
+                "<span{} class=\"data\">{}</span>",
+                id,
+                data_html(*addr, *len, *kind, spc)
+            )
+        }
+        Item::Unknown(DecodeError::InvalidOpcode { pc, opcode }) => writeln!(
+            writer,
+            "<span class=\"unknown\">{:04x}: db {:02x}    ; unknown opcode</span>",
+            pc, opcode
+        ),
+        Item::Unknown(DecodeError::UnknownByte { pc, .. }) => writeln!(
+            writer,
+            "<span class=\"unknown\">{:04x}: db ??    ; unknown byte</span>",
+            pc
+        ),
+        Item::Break => writeln!(writer),
+    })?;
+    writeln!(writer, "{}", PAGE_END)
+}
+
+fn anchor(addr: u16) -> String {
+    format!("a{:04x}", addr)
+}
+
+/// The opening tag of a link to the line at `addr`, if the listing has
+/// one.
+fn link(addr: u16, spc: &Spc) -> Option<String> {
+    spc.lines
+        .contains(&addr)
+        .then(|| format!("<a href=\"#{}\">", anchor(addr)))
+}
+
+/// `html` linked to the line at `addr`, or left as it is if there is none.
+fn linked(addr: u16, html: String, spc: &Spc) -> String {
+    match link(addr, spc) {
+        Some(open) => format!("{}{}</a>", open, html),
+        None => html,
+    }
+}
+
+/// Writes the block comment and label line that go above `addr`. The
+/// anchor for `addr` goes on the label; otherwise the returned `id`
+/// attribute is for the line itself.
+fn write_notes(writer: &mut dyn Write, addr: u16, spc: &Spc) -> io::Result<String> {
+    let id = format!(" id=\"{}\"", anchor(addr));
+    for line in spc.comments.block(addr) {
+        let text = if line.is_empty() {
+            ";".to_string()
+        } else {
+            format!("; {}", line)
+        };
+        writeln!(writer, "<span class=\"comment\">{}</span>", escape(&text))?;
+    }
+    let label = spc
+        .labels
+        .get(addr)
+        .map(|name| format!("<span{} class=\"label\">{}:</span>", id, escape(name)));
+    let comment =
+        comment_html(addr, spc).map(|html| format!("<span class=\"comment\">; {}</span>", html));
+    let has_label = label.is_some();
+    match (label, comment) {
+        (Some(label), Some(comment)) => writeln!(writer, "{} {}", label, comment)?,
+        (Some(label), None) => writeln!(writer, "{}", label)?,
+        (None, Some(comment)) => writeln!(writer, "{}", comment)?,
+        (None, None) => {}
+    }
+    Ok(if has_label { String::new() } else { id })
+}
+
+/// The label comment at `addr` with each cross-reference linked to the
+/// line it comes from.
+fn comment_html(addr: u16, spc: &Spc) -> Option<String> {
+    let xrefs = spc.xrefs.get(addr);
+    let xrefs = (spc.config.show_xrefs && !xrefs.is_empty()).then(|| {
+        let links: Vec<String> = xrefs
+            .iter()
+            .map(|xref| linked(xref.from, escape(&xref.to_string()), spc))
+            .collect();
+        format!("xref: {}", links.join(", "))
+    });
+    match (spc.comments.label(addr), xrefs) {
+        (Some(comment), Some(xrefs)) => Some(format!("{}; {}", escape(comment), xrefs)),
+        (Some(comment), None) => Some(escape(comment)),
+        (None, xrefs) => xrefs,
+    }
+}
+
+fn instruction_html(instr: &DecodedInstruction, spc: &Spc) -> String {
+    let disassembly = formatter::format_disassembly(instr, spc);
+    let comments = formatter::format_comments(instr, spc).join("; ");
+    let mut code_marks = Vec::new();
+    let mut comment_marks = Vec::new();
+
+    // A `jmp ($xxxx+x)` links to its table.
+    let target = match instr.operand {
+        DecodedOperand::AbsoluteX(table) if instr.flow() == FlowKind::IndirectJump => Some(table),
+        _ => instr
+            .branch_target()
+            .or_else(|| flow::call_target(instr, spc)),
+    };
+    if let Some(target) = target
+        && let Some(open) = link(target, spc)
+    {
+        let name = formatter::format_abs(target, spc);
+        // tcall and pcall show their target in the comment instead.
+        if let Some(range) = find_token(&disassembly, &name) {
+            code_marks.push((range, open, "</a>"));
+        } else if let Some(range) = find_token(&comments, &name) {
+            comment_marks.push((range, open, "</a>"));
+        }
+    }
+
+    for addr in instr.operand_addresses(spc.direct_page) {
+        let Some(reg) = hwregs::lookup(addr) else {
+            continue;
+        };
+        let mut title = format!("{}: {}", reg.name, reg.description);
+        if addr == DSPDATA
+            && let Some(dsp) = spc.dsp_tracker.annotate(instr, spc.direct_page)
+        {
+            title = format!("{}\n{}", title, dsp);
+        }
+        let names = [
+            spc.labels.get(addr).map(str::to_string),
+            Some(reg.name.to_string()),
+            Some(format!("${:02x}", addr & 0xFF)),
+            Some(format!("${:04x}", addr)),
+        ];
+        if let Some(range) = names
+            .iter()
+            .flatten()
+            .find_map(|name| find_token(&disassembly, name))
+        {
+            let open = format!("<span class=\"reg\" title=\"{}\">", escape(&title));
+            code_marks.push((range, open, "</span>"));
+        }
+    }
+
+    let mut html = escape(&formatter::format_prefix(instr, spc));
+    html.push_str(&decorate(&disassembly, code_marks));
+    if !comments.is_empty() {
+        let width = COMMENT_COLUMN - 1;
+        let padding = width.saturating_sub(disassembly.chars().count());
+        html.push_str(&" ".repeat(padding));
+        html.push_str(&format!(
+            " <span class=\"comment\">; {}</span>",
+            decorate(&comments, comment_marks)
+        ));
+    }
+    html
+}
+
+fn data_html(addr: u16, len: u16, kind: DataKind, spc: &Spc) -> String {
+    let (directive, items) = formatter::format_data_items(addr, len, kind, spc);
+    let items: Vec<String> = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            if kind == DataKind::Pointers {
+                let target = spc.read_word(addr.wrapping_add(2 * i as u16));
+                linked(target, escape(item), spc)
+            } else {
+                escape(item)
+            }
+        })
+        .collect();
+    let mut html = escape(&formatter::format_data_prefix(addr, spc));
+    html.push_str(&format!("{:<5} {}", directive, items.join(",")));
+    if let Some(comment) = spc.comments.inline(addr) {
+        html.push_str(&format!(
+            " <span class=\"comment\">; {}</span>",
+            escape(comment)
+        ));
+    }
+    html
+}
+
+/// Finds `name` in `text` as a whole token, not as part of a longer name
+/// or number.
+fn find_token(text: &str, name: &str) -> Option<Range<usize>> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    text.match_indices(name).find_map(|(start, _)| {
+        let end = start + name.len();
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        let clear =
+            before.is_none_or(|c| !is_word(c) && c != '$') && after.is_none_or(|c| !is_word(c));
+        clear.then_some(start..end)
+    })
+}
+
+/// Escapes `text`, wrapping each marked range in its tags. Marks that
+/// overlap an earlier one are dropped.
+fn decorate(text: &str, mut marks: Vec<(Range<usize>, String, &str)>) -> String {
+    marks.sort_by_key(|(range, _, _)| range.start);
+    let mut html = String::new();
+    let mut pos = 0;
+    for (range, open, close) in marks {
+        if range.start < pos {
+            continue;
+        }
+        html.push_str(&escape(&text[pos..range.start]));
+        html.push_str(&open);
+        html.push_str(&escape(&text[range.clone()]));
+        html.push_str(close);
+        pos = range.end;
+    }
+    html.push_str(&escape(&text[pos..]));
+    html
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spc::Config;
+
+    #[test]
+    fn find_token_matches_whole_names() {
+        assert_eq!(find_token("mov a,loop_2", "loop_2"), Some(6..12));
+        assert_eq!(find_token("bne loop_2", "loop"), None);
+        assert_eq!(find_token("mov a,$f2+x", "f2"), None);
+        assert_eq!(find_token("call $0a00", "$0a00"), Some(5..10));
+        assert_eq!(find_token("jmp x_loop,loop", "loop"), Some(11..15));
+    }
+
+    #[test]
+    fn decorate_escapes_and_skips_overlapping_marks() {
+        let marks = vec![
+            (6..10, "<a href=\"#a0800\">".to_string(), "</a>"),
+            (4..8, "<b>".to_string(), "</b>"),
+            (0..3, "<i>".to_string(), "</i>"),
+        ];
+        assert_eq!(
+            decorate("a<b c<&>d", marks),
+            "<i>a&lt;b</i> <b>c&lt;&amp;&gt;</b>d"
+        );
+    }
+
+    #[test]
+    fn escape_replaces_markup_characters() {
+        assert_eq!(
+            escape(r#"<a href="x">&</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+        assert_eq!(escape("mov a,$12"), "mov a,$12");
+    }
+
+    #[test]
+    fn links_only_to_listed_lines() {
+        let code = [
+            0x2F, 0xFE, // bra $0800
+            0x3F, 0x00, 0x09, // call $0900
+        ];
+        let config = Config {
+            show_hex: false,
+            ..Config::default()
+        };
+        let mut spc = Spc::new(config, 0x0800, 0x0805);
+        spc.load_rom(&code, 0x0800);
+        let mut html = Vec::new();
+        write_html(&mut html, &mut spc, None).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(
+            html.contains("<span id=\"a0800\">0800: bra   <a href=\"#a0800\">$0800</a></span>")
+        );
+        assert!(html.contains("<span id=\"a0802\">0802: call  $0900</span>"));
+    }
+}
@@ -0,0 +1,40 @@
+pub struct HwRegister {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+macro_rules! reg {
+    ($name:expr, $desc:expr) => {
+        HwRegister {
+            name: $name,
+            description: $desc,
+        }
+    };
+}
+
+/// The SPC700's memory-mapped I/O registers at $00F0-$00FF.
+pub static HW_REGISTERS: [HwRegister; 16] = [
+    reg!("TEST", "testing functions, do not write"),
+    reg!("CONTROL", "timer enable, input port clear, IPL ROM enable"),
+    reg!("DSPADDR", "S-DSP register address"),
+    reg!("DSPDATA", "S-DSP register data"),
+    reg!("CPUIO0", "S-CPU I/O port 0 ($2140)"),
+    reg!("CPUIO1", "S-CPU I/O port 1 ($2141)"),
+    reg!("CPUIO2", "S-CPU I/O port 2 ($2142)"),
+    reg!("CPUIO3", "S-CPU I/O port 3 ($2143)"),
+    reg!("AUXIO4", "auxiliary I/O port 4, general purpose RAM"),
+    reg!("AUXIO5", "auxiliary I/O port 5, general purpose RAM"),
+    reg!("T0TARGET", "timer 0 divisor (8 kHz)"),
+    reg!("T1TARGET", "timer 1 divisor (8 kHz)"),
+    reg!("T2TARGET", "timer 2 divisor (64 kHz)"),
+    reg!("T0OUT", "timer 0 output, 4 bits, cleared on read"),
+    reg!("T1OUT", "timer 1 output, 4 bits, cleared on read"),
+    reg!("T2OUT", "timer 2 output, 4 bits, cleared on read"),
+];
+
+pub fn lookup(addr: u16) -> Option<&'static HwRegister> {
+    match addr {
+        0x00F0..=0x00FF => Some(&HW_REGISTERS[(addr - 0x00F0) as usize]),
+        _ => None,
+    }
+}
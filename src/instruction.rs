@@ -34,7 +34,7 @@ pub struct OpcodeDef {
     pub mnemonic: &'static str,
     pub mode: AddressingModeKind,
     pub len: u8,
-    #[allow(dead_code)]
+    /// Base cycle count; conditional branches take two more when taken.
     pub cycles: u8,
 }
 
@@ -338,7 +338,7 @@ pub static OPCODES: [OpcodeDef; 256] = {
     table
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum DecodedOperand {
     None,
     Byte(u8),
@@ -364,9 +364,82 @@ pub enum DecodedOperand {
     DirectXRelative { addr: u8, offset: i8 },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct DecodedInstruction {
     pub address: u16,
     pub definition: &'static OpcodeDef,
     pub operand: DecodedOperand,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowKind {
+    /// Execution continues with the next instruction.
+    Sequential,
+    /// Conditional branch: either the branch target or the next instruction.
+    Branch,
+    /// Unconditional transfer to a known target.
+    Jump,
+    /// Unconditional transfer through a table in memory (`jmp (!abs+x)`).
+    IndirectJump,
+    /// Subroutine call; execution resumes with the next instruction.
+    Call,
+    Return,
+    Halt,
+}
+
+impl DecodedInstruction {
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.definition.len as u16)
+    }
+
+    /// Resolved destination of a relative branch operand, if any.
+    pub fn branch_target(&self) -> Option<u16> {
+        let offset = match self.operand {
+            DecodedOperand::Relative(offset)
+            | DecodedOperand::DirectBitRelative { offset, .. }
+            | DecodedOperand::DirectRelative { offset, .. }
+            | DecodedOperand::DirectXRelative { offset, .. } => offset,
+            _ => return None,
+        };
+        Some(self.next_address().wrapping_add(offset as i16 as u16))
+    }
+
+    /// Memory addresses accessed through the operand, with direct-page
+    /// operands placed in `direct_page`. Jump and call targets are excluded.
+    pub fn operand_addresses(&self, direct_page: u16) -> Vec<u16> {
+        let dp = |addr: u8| direct_page | addr as u16;
+        match self.operand {
+            DecodedOperand::Direct(addr)
+            | DecodedOperand::DirectX(addr)
+            | DecodedOperand::DirectY(addr)
+            | DecodedOperand::IndirectX(addr)
+            | DecodedOperand::IndirectY(addr)
+            | DecodedOperand::DirectBit { addr }
+            | DecodedOperand::DirectBitRelative { addr, .. }
+            | DecodedOperand::DpImm { addr, .. }
+            | DecodedOperand::DirectRelative { addr, .. }
+            | DecodedOperand::DirectXRelative { addr, .. } => vec![dp(addr)],
+            DecodedOperand::DpToDp { dest, src } => vec![dp(dest), dp(src)],
+            DecodedOperand::Absolute(addr) if !matches!(self.definition.opcode, 0x3F | 0x5F) => {
+                vec![addr]
+            }
+            DecodedOperand::AbsoluteX(addr)
+            | DecodedOperand::AbsoluteY(addr)
+            | DecodedOperand::MemoryBit { addr, .. } => vec![addr],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn flow(&self) -> FlowKind {
+        match self.definition.opcode {
+            0x2F | 0x5F => FlowKind::Jump,
+            0x1F => FlowKind::IndirectJump,
+            0x3F | 0x4F | 0x0F => FlowKind::Call,
+            op if op & 0x0F == 0x01 => FlowKind::Call,
+            0x6F | 0x7F => FlowKind::Return,
+            0xEF | 0xFF => FlowKind::Halt,
+            _ if self.branch_target().is_some() => FlowKind::Branch,
+            _ => FlowKind::Sequential,
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::data::{DataKind, DataRegion};
+use crate::labels::Labels;
+use crate::spc::Spc;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Address the IPL ROM is mapped at while CONTROL ($F1) bit 7 is set.
+pub const IPL_ADDR: u16 = 0xFFC0;
+
+/// The 64-byte boot ROM: clears the zero page, signals $BBAA on ports 0/1
+/// and then receives blocks from the S-CPU until told to jump.
+pub const IPL_ROM: [u8; 64] = [
+    0xCD, 0xEF, 0xBD, 0xE8, 0x00, 0xC6, 0x1D, 0xD0, 0xFC, 0x8F, 0xAA, 0xF4, 0x8F, 0xBB, 0xF5, 0x78,
+    0xCC, 0xF4, 0xD0, 0xFB, 0x2F, 0x19, 0xEB, 0xF4, 0xD0, 0xFC, 0x7E, 0xF4, 0xD0, 0x0B, 0xE4, 0xF5,
+    0xCB, 0xF4, 0xD7, 0x00, 0xFC, 0xD0, 0xF3, 0xAB, 0x01, 0x10, 0xEF, 0x7E, 0xF4, 0x10, 0xEB, 0xBA,
+    0xF6, 0xDA, 0x00, 0xBA, 0xF4, 0xC4, 0xF4, 0xDD, 0x5D, 0xD0, 0xDB, 0x1F, 0x00, 0x00, 0xC0, 0xFF,
+];
+
+/// Well-known places in the ROM that drivers jump back to or describe.
+pub const ENTRY_POINTS: &[(u16, &str)] = &[
+    (0xFFC0, "ipl_reset"),
+    (0xFFC9, "ipl_ready"),
+    (0xFFCF, "ipl_wait_start"),
+    (0xFFD6, "ipl_next_block"),
+    (0xFFDA, "ipl_recv_byte"),
+    (0xFFEF, "ipl_block_header"),
+    (0xFFFB, "ipl_jump"),
+    (0xFFFE, "ipl_reset_vector"),
+];
+
+/// Whether to show the IPL ROM at $FFC0-$FFFF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IplMode {
+    On,
+    Off,
+    /// Follows CONTROL ($F1) bit 7 of a loaded `.spc` snapshot; off for
+    /// raw binaries, which do not set CONTROL.
+    Auto,
+}
+
+impl IplMode {
+    /// Whether the ROM should be mapped, given the CONTROL value from a
+    /// loaded `.spc` snapshot, if any.
+    pub fn rom_enabled(self, control: Option<u8>) -> bool {
+        match self {
+            IplMode::On => true,
+            IplMode::Off => false,
+            IplMode::Auto => control.is_some_and(|control| control & 0x80 != 0),
+        }
+    }
+}
+
+impl FromStr for IplMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "on" => Ok(IplMode::On),
+            "off" => Ok(IplMode::Off),
+            "auto" => Ok(IplMode::Auto),
+            _ => Err(format!(
+                "unknown IPL mode '{}' (expected on, off or auto)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for IplMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IplMode::On => "on",
+            IplMode::Off => "off",
+            IplMode::Auto => "auto",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Maps the ROM over $FFC0-$FFFF, moving the RAM beneath it from `spc.mem`
+/// to `spc.ipl_ram`. Does nothing if the ROM is already mapped.
+pub fn map_rom(spc: &mut Spc) {
+    if spc.ipl_rom_mapped {
+        return;
+    }
+    let base = IPL_ADDR as usize;
+    spc.ipl_ram.copy_from_slice(&spc.mem[base..]);
+    spc.mem[base..].copy_from_slice(&IPL_ROM);
+    spc.ipl_rom_mapped = true;
+}
+
+/// Puts the RAM kept in `spc.ipl_ram` back at $FFC0-$FFFF.
+pub fn unmap_rom(spc: &mut Spc) {
+    if !spc.ipl_rom_mapped {
+        return;
+    }
+    let base = IPL_ADDR as usize;
+    spc.mem[base..].copy_from_slice(&spc.ipl_ram);
+    spc.ipl_rom_mapped = false;
+}
+
+/// The reset vector at $FFFE, listed as a pointer to `ipl_reset`.
+pub fn reset_vector() -> DataRegion {
+    DataRegion {
+        start: 0xFFFE,
+        end: 0x0000,
+        kind: DataKind::Pointers,
+    }
+}
+
+/// Names the ROM's entry points where `labels` has no name yet.
+pub fn add_labels(labels: &mut Labels) {
+    let mut ipl = Labels::default();
+    for &(addr, name) in ENTRY_POINTS {
+        ipl.insert(addr, name.to_string());
+    }
+    labels.merge_missing(ipl);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_follows_control_only_from_a_snapshot() {
+        assert!(IplMode::Auto.rom_enabled(Some(0x80)));
+        assert!(!IplMode::Auto.rom_enabled(Some(0x7F)));
+        assert!(!IplMode::Auto.rom_enabled(None));
+        assert!(IplMode::On.rom_enabled(None));
+        assert!(!IplMode::Off.rom_enabled(Some(0x80)));
+    }
+}
@@ -0,0 +1,248 @@
+use crate::cpu::BRANCH_TAKEN_CYCLES;
+use crate::decoder::DecodeError;
+use crate::flow::{self, CodeMap};
+use crate::formatter;
+use crate::instruction::{AddressingModeKind, DecodedInstruction, DecodedOperand, FlowKind};
+use crate::listing::{self, Item};
+use crate::spc::Spc;
+use serde_json::{Map, Value, json};
+use std::io::{self, Write};
+
+/// Writes the listing as a JSON array, one object per line of the listing.
+pub fn write_json(writer: &mut dyn Write, spc: &mut Spc, code: Option<&CodeMap>) -> io::Result<()> {
+    write!(writer, "[")?;
+    let mut first = true;
+    listing::walk(spc, code, |item, spc| {
+        let Some(record) = item_json(item, spc) else {
+            return Ok(());
+        };
+        let separator = if first { "" } else { "," };
+        first = false;
+        write!(writer, "{}\n{}", separator, record)
+    })?;
+    writeln!(writer, "\n]")
+}
+
+/// Writes the listing as JSON Lines, one object per line.
+pub fn write_json_lines(
+    writer: &mut dyn Write,
+    spc: &mut Spc,
+    code: Option<&CodeMap>,
+) -> io::Result<()> {
+    listing::walk(spc, code, |item, spc| match item_json(item, spc) {
+        Some(record) => writeln!(writer, "{}", record),
+        None => Ok(()),
+    })
+}
+
+/// The JSON object for one listing item; the gap between ranges has none.
+///
+/// Every object has `type` (`instruction`, `data` or `unknown`),
+/// `address` and `bytes`. Instructions add `opcode`, `mnemonic`, `mode`,
+/// `operand`, `text`, `target`, `length`, `cycles` and `cycles_taken`;
+/// data adds `kind`, `length` and `text`. Instructions and data carry
+/// `label`, `label_comment`, `block_comment`, `comments` and `xrefs`, the
+/// references to the line as `{"from": ..., "kind": ...}`.
+pub fn item_json(item: &Item, spc: &Spc) -> Option<Value> {
+    let bytes = |addr: u16, len: u16| -> Vec<u8> {
+        (0..len)
+            .map(|i| spc.read_byte(addr.wrapping_add(i)))
+            .collect()
+    };
+    let record = match *item {
+        Item::Instruction(ref instr) => {
+            let def = instr.definition;
+            let target = instr
+                .branch_target()
+                .or_else(|| flow::call_target(instr, spc));
+            let mut record = json!({
+                "type": "instruction",
+                "address": instr.address,
+                "bytes": bytes(instr.address, def.len as u16),
+                "opcode": def.opcode,
+                "mnemonic": def.mnemonic,
+                "mode": mode_name(def.mode),
+                "operand": operand_json(instr),
+                "text": formatter::format_disassembly(instr, spc),
+                "target": target,
+                "target_label": target.and_then(|target| spc.labels.get(target)),
+                "length": def.len,
+                "cycles": def.cycles,
+                "cycles_taken": (instr.flow() == FlowKind::Branch)
+                    .then_some(def.cycles + BRANCH_TAKEN_CYCLES),
+            });
+            add_notes(&mut record, instr.address, spc);
+            record["comments"] = json!(formatter::format_comments(instr, spc));
+            record
+        }
+        Item::Data { addr, len, kind } => {
+            let mut record = json!({
+                "type": "data",
+                "address": addr,
+                "bytes": bytes(addr, len),
+                "kind": kind.to_string(),
+                "length": len,
+                "text": formatter::format_data_directive(addr, len, kind, spc),
+            });
+            add_notes(&mut record, addr, spc);
+            record["comments"] = json!(spc.comments.inline(addr).into_iter().collect::<Vec<_>>());
+            record
+        }
+        Item::Unknown(DecodeError::InvalidOpcode { pc, opcode }) => json!({
+            "type": "unknown",
+            "address": pc,
+            "bytes": [opcode],
+        }),
+        Item::Unknown(DecodeError::UnknownByte { pc, .. }) => json!({
+            "type": "unknown",
+            "address": pc,
+            "bytes": [],
+        }),
+        Item::Break => return None,
+    };
+    Some(record)
+}
+
+/// Adds the label, block comment and cross-references at `addr`.
+fn add_notes(record: &mut Value, addr: u16, spc: &Spc) {
+    record["label"] = json!(spc.labels.get(addr));
+    record["label_comment"] = json!(spc.comments.label(addr));
+    record["block_comment"] = json!(spc.comments.block(addr));
+    record["xrefs"] = spc
+        .xrefs
+        .get(addr)
+        .iter()
+        .map(|xref| json!({ "from": xref.from, "kind": xref.kind.to_string() }))
+        .collect();
+}
+
+fn mode_name(mode: AddressingModeKind) -> String {
+    match mode {
+        AddressingModeKind::TCall(_) => "TCall".to_string(),
+        _ => format!("{:?}", mode),
+    }
+}
+
+/// The decoded operand as `{"kind": ..., fields}`; relative offsets are
+/// signed.
+fn operand_json(instr: &DecodedInstruction) -> Value {
+    let (kind, fields) = match instr.operand {
+        DecodedOperand::None => ("None", json!({})),
+        DecodedOperand::Byte(value) => ("Byte", json!({ "value": value })),
+        DecodedOperand::Direct(addr) => ("Direct", json!({ "addr": addr })),
+        DecodedOperand::Absolute(addr) => ("Absolute", json!({ "addr": addr })),
+        DecodedOperand::DirectX(addr) => ("DirectX", json!({ "addr": addr })),
+        DecodedOperand::DirectY(addr) => ("DirectY", json!({ "addr": addr })),
+        DecodedOperand::AbsoluteX(addr) => ("AbsoluteX", json!({ "addr": addr })),
+        DecodedOperand::AbsoluteY(addr) => ("AbsoluteY", json!({ "addr": addr })),
+        DecodedOperand::IndirectX(addr) => ("IndirectX", json!({ "addr": addr })),
+        DecodedOperand::IndirectY(addr) => ("IndirectY", json!({ "addr": addr })),
+        DecodedOperand::Indirect => ("Indirect", json!({})),
+        DecodedOperand::IndirectAutoInc => ("IndirectAutoInc", json!({})),
+        DecodedOperand::Relative(offset) => ("Relative", json!({ "offset": offset })),
+        DecodedOperand::DirectBit { addr } => (
+            "DirectBit",
+            json!({ "addr": addr, "bit": instr.definition.opcode >> 5 }),
+        ),
+        DecodedOperand::DirectBitRelative { addr, offset } => (
+            "DirectBitRelative",
+            json!({ "addr": addr, "bit": instr.definition.opcode >> 5, "offset": offset }),
+        ),
+        DecodedOperand::MemoryBit { addr, bit } => {
+            ("MemoryBit", json!({ "addr": addr, "bit": bit }))
+        }
+        DecodedOperand::DpToDp { dest, src } => ("DpToDp", json!({ "dest": dest, "src": src })),
+        DecodedOperand::DpImm { addr, imm } => ("DpImm", json!({ "addr": addr, "imm": imm })),
+        DecodedOperand::TCall(n) => ("TCall", json!({ "vector": n })),
+        DecodedOperand::PCall(addr) => ("PCall", json!({ "addr": addr })),
+        DecodedOperand::DirectRelative { addr, offset } => {
+            ("DirectRelative", json!({ "addr": addr, "offset": offset }))
+        }
+        DecodedOperand::DirectXRelative { addr, offset } => {
+            ("DirectXRelative", json!({ "addr": addr, "offset": offset }))
+        }
+    };
+    let mut operand = Map::new();
+    operand.insert("kind".to_string(), json!(kind));
+    if let Value::Object(fields) = fields {
+        operand.extend(fields);
+    }
+    Value::Object(operand)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DataKind;
+    use crate::decoder::decode_one;
+    use crate::spc::Config;
+
+    #[test]
+    fn records_keep_their_schema() {
+        let code = [
+            0xB0, 0xFC, // bcs $07fe
+            0x34, 0x12, // dw $1234
+        ];
+        let mut spc = Spc::new(Config::default(), 0x0800, 0x0804);
+        spc.load_rom(&code, 0x0800);
+        spc.labels.insert(0x07FE, "loop".to_string());
+        spc.comments.set_inline(0x0800, "retry".to_string());
+
+        let bcs = Item::Instruction(decode_one(&spc, 0x0800).unwrap());
+        assert_eq!(
+            item_json(&bcs, &spc).unwrap(),
+            json!({
+                "type": "instruction",
+                "address": 0x0800,
+                "bytes": [0xB0, 0xFC],
+                "opcode": 0xB0,
+                "mnemonic": "bcs",
+                "mode": "Relative",
+                "operand": { "kind": "Relative", "offset": -4 },
+                "text": "bcs   loop",
+                "target": 0x07FE,
+                "target_label": "loop",
+                "length": 2,
+                "cycles": 2,
+                "cycles_taken": 4,
+                "label": null,
+                "label_comment": null,
+                "block_comment": [],
+                "xrefs": [],
+                "comments": ["retry"],
+            })
+        );
+
+        let data = Item::Data {
+            addr: 0x0802,
+            len: 2,
+            kind: DataKind::Words,
+        };
+        assert_eq!(
+            item_json(&data, &spc).unwrap(),
+            json!({
+                "type": "data",
+                "address": 0x0802,
+                "bytes": [0x34, 0x12],
+                "kind": "word",
+                "length": 2,
+                "text": "dw    $1234",
+                "label": null,
+                "label_comment": null,
+                "block_comment": [],
+                "xrefs": [],
+                "comments": [],
+            })
+        );
+
+        let unknown = Item::Unknown(DecodeError::InvalidOpcode {
+            pc: 0x0804,
+            opcode: 0xFF,
+        });
+        assert_eq!(
+            item_json(&unknown, &spc).unwrap(),
+            json!({ "type": "unknown", "address": 0x0804, "bytes": [0xFF] })
+        );
+        assert_eq!(item_json(&Item::Break, &spc), None);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::flow::TargetKind;
+use crate::instruction::DecodedInstruction;
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Default)]
+pub struct Labels {
+    names: BTreeMap<u16, String>,
+}
+
+impl Labels {
+    pub fn get(&self, addr: u16) -> Option<&str> {
+        self.names.get(&addr).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, addr: u16, name: String) {
+        self.names.insert(addr, name);
+    }
+
+    /// Adds the names from `other` for addresses that have none yet.
+    pub fn merge_missing(&mut self, other: Labels) {
+        for (addr, name) in other.names {
+            self.names.entry(addr).or_insert(name);
+        }
+    }
+}
+
+/// Names every target in `targets` that starts one of `instrs`:
+/// `sub_XXXX` for subroutine entry points and `loc_XXXX` for the rest.
+pub fn generate(instrs: &[DecodedInstruction], targets: &BTreeMap<u16, TargetKind>) -> Labels {
+    let starts: HashSet<u16> = instrs.iter().map(|instr| instr.address).collect();
+    let mut labels = Labels::default();
+    for (&addr, &kind) in targets {
+        if starts.contains(&addr) {
+            let prefix = match kind {
+                TargetKind::Call => "sub",
+                TargetKind::Branch => "loc",
+            };
+            labels.insert(addr, format!("{}_{:04X}", prefix, addr));
+        }
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::decode_range;
+    use crate::flow;
+    use crate::spc::{Config, Spc};
+
+    #[test]
+    fn names_listed_targets_without_replacing_symbols() {
+        let code = [
+            0x3F, 0x08, 0x02, // call $0208
+            0xD0, 0x01, // bne $0206
+            0x6F, // ret
+            0x2F, 0x00, // bra $0208
+            0x00, // nop
+            0x5F, 0x00, 0x03, // jmp $0300
+            0x3F, 0x10, 0x02, // call $0210
+            0x6F, // ret
+            0x6F, // ret
+        ];
+        let mut spc = Spc::new(Config::default(), 0x0200, 0x0211);
+        spc.load_rom(&code, 0x0200);
+        let instrs: Vec<_> = decode_range(&spc, 0x0200, 0x0211)
+            .map(Result::unwrap)
+            .collect();
+        let generated = generate(&instrs, &flow::targets(&instrs, &spc));
+
+        let mut labels = Labels::default();
+        labels.insert(0x0210, "play".to_string());
+        labels.merge_missing(generated);
+        assert_eq!(
+            labels.names.into_iter().collect::<Vec<_>>(),
+            [
+                (0x0206, "loc_0206".to_string()),
+                // Called as well as branched to.
+                (0x0208, "sub_0208".to_string()),
+                (0x0210, "play".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! SPC700 disassembler library.
+//!
+//! Decode single instructions with [`decode`] or [`decoder::decode_one`]
+//! from anything implementing [`Memory`], walk a range with a
+//! [`Disassembler`], and turn the results into text with
+//! [`formatter::format_instruction`] using the [`Config`] stored on an
+//! [`Spc`].
+
+pub mod assembler;
+pub mod comments;
+pub mod cpu;
+pub mod data;
+pub mod decoder;
+pub mod disassembler;
+pub mod dsp;
+pub mod flow;
+pub mod formatter;
+pub mod html;
+pub mod hwregs;
+pub mod instruction;
+pub mod ipl;
+pub mod json;
+pub mod labels;
+pub mod listing;
+pub mod memory;
+pub mod project;
+pub mod spc;
+pub mod spcfile;
+pub mod symbols;
+pub mod syntax;
+pub mod trace;
+pub mod xrefs;
+
+pub use cpu::Cpu;
+pub use decoder::{DecodeError, decode_one, decode_range};
+pub use disassembler::Disassembler;
+pub use formatter::format_instruction;
+pub use instruction::{
+    AddressingModeKind, DecodedInstruction, DecodedOperand, FlowKind, OPCODES, OpcodeDef,
+};
+pub use memory::{Memory, Overlay, SliceMemory, SparseMemory};
+pub use spc::{Config, Registers, Spc};
+
+use std::num::ParseIntError;
+
+/// Decodes the instruction at the start of `bytes`, as if they were loaded at
+/// `address`.
+pub fn decode(bytes: &[u8], address: u16) -> Result<DecodedInstruction, DecodeError> {
+    decode_one(&SliceMemory::new(bytes, address), address)
+}
+
+/// Parses a hex address written as `0800`, `$0800` or `0x0800`.
+pub fn parse_addr(s: &str) -> Result<u16, ParseIntError> {
+    let hex = s
+        .strip_prefix('$')
+        .or_else(|| s.strip_prefix("0x"))
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u16::from_str_radix(hex, 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_addr_accepts_hex_prefixes() {
+        for s in ["0800", "$0800", "0x0800", "0X0800", "800"] {
+            assert_eq!(parse_addr(s), Ok(0x0800), "{}", s);
+        }
+        for s in ["", "$", "0x", "$$0800", "10000", "08g0", "-800"] {
+            assert!(parse_addr(s).is_err(), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,435 @@
+use crate::data::DataKind;
+use crate::decoder::DecodeError;
+use crate::disassembler::Disassembler;
+use crate::flow::{self, CodeMap, TargetKind};
+use crate::formatter;
+use crate::html;
+use crate::instruction::{DecodedInstruction, FlowKind};
+use crate::json;
+use crate::labels;
+use crate::spc::{AddressRange, Spc};
+use crate::xrefs;
+use serde::Deserialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// One line of a listing.
+#[derive(Debug)]
+pub enum Item {
+    Instruction(DecodedInstruction),
+    Data {
+        addr: u16,
+        len: u16,
+        kind: DataKind,
+    },
+    Unknown(DecodeError),
+    /// The gap between two listed ranges.
+    Break,
+}
+
+/// Output formats for a listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Text,
+    /// A JSON array with one object per line of the listing.
+    Json,
+    /// One JSON object per line.
+    Jsonl,
+    /// A self-contained HTML page with cross-linked labels.
+    Html,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "jsonl" | "json-lines" => Ok(Format::Jsonl),
+            "html" => Ok(Format::Html),
+            _ => Err(format!(
+                "unknown format '{}' (expected text, json, jsonl or html)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Text => "text",
+            Format::Json => "json",
+            Format::Jsonl => "jsonl",
+            Format::Html => "html",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Writes the listing in `format`.
+pub fn write(
+    writer: &mut dyn Write,
+    spc: &mut Spc,
+    code: Option<&CodeMap>,
+    format: Format,
+) -> io::Result<()> {
+    match format {
+        Format::Text => write_listing(writer, spc, code),
+        Format::Json => json::write_json(writer, spc, code),
+        Format::Jsonl => json::write_json_lines(writer, spc, code),
+        Format::Html => html::write_html(writer, spc, code),
+    }
+}
+
+/// Writes the listing for `spc.listing_ranges()`, with a blank line between
+/// ranges and labels shared across all of them. Declared data regions are
+/// emitted as data directives. With a code map, only reachable
+/// instructions are decoded and every other byte is emitted as `db` data;
+/// without one the range is swept linearly.
+pub fn write_listing(
+    writer: &mut dyn Write,
+    spc: &mut Spc,
+    code: Option<&CodeMap>,
+) -> io::Result<()> {
+    if let Some(header) = spc.config.dialect.syntax().header {
+        writeln!(writer, "{}", header)?;
+    }
+    walk(spc, code, |item, spc| match item {
+        Item::Instruction(instr) => {
+            write_label(writer, instr.address, spc)?;
+            let line = formatter::format_instruction(instr, spc);
+            writeln!(writer, "{}", line)
+        }
+        Item::Data { addr, len, kind } => {
+            write_label(writer, *addr, spc)?;
+            let line = formatter::format_data(*addr, *len, *kind, spc);
+            writeln!(writer, "{}", line)
+        }
+        Item::Unknown(DecodeError::InvalidOpcode { pc, opcode }) => {
+            writeln!(writer, "{:04x}: db {:02x}    ; unknown opcode", pc, opcode)
+        }
+        Item::Unknown(DecodeError::UnknownByte { pc, .. }) => {
+            writeln!(writer, "{:04x}: db ??    ; unknown byte", pc)
+        }
+        Item::Break => writeln!(writer),
+    })
+}
+
+/// Decodes the listed ranges, names their targets when labels are enabled,
+/// records their cross-references in `spc.xrefs`, and calls `visit` for
+/// each line in order. `spc`'s direct page, DSP tracking and block cycle
+/// count are kept up to date, so `visit` sees the same state the text
+/// listing is formatted with.
+pub fn walk(
+    spc: &mut Spc,
+    code: Option<&CodeMap>,
+    mut visit: impl FnMut(&Item, &Spc) -> io::Result<()>,
+) -> io::Result<()> {
+    let items = collect_items(spc, code);
+    let instrs: Vec<DecodedInstruction> = items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Instruction(instr) => Some(*instr),
+            _ => None,
+        })
+        .collect();
+    spc.xrefs = xrefs::collect(&items, spc);
+    spc.lines = items
+        .iter()
+        .filter_map(|item| match *item {
+            Item::Instruction(ref instr) => Some(instr.address),
+            Item::Data { addr, .. } => Some(addr),
+            _ => None,
+        })
+        .collect();
+    let mut targets = flow::targets(&instrs, spc);
+    for item in &items {
+        if let Item::Data {
+            addr,
+            len,
+            kind: DataKind::Pointers,
+        } = *item
+        {
+            for i in (0..len).step_by(2) {
+                let target = spc.read_word(addr.wrapping_add(i));
+                targets.entry(target).or_insert(TargetKind::Branch);
+            }
+        }
+    }
+
+    if spc.config.gen_labels {
+        let generated = labels::generate(&instrs, &targets);
+        spc.labels.merge_missing(generated);
+    }
+    // Bytes inside an instruction have no line to comment.
+    for instr in &instrs {
+        for i in 1..instr.definition.len as u16 {
+            spc.comments
+                .move_to(instr.address.wrapping_add(i), instr.address);
+        }
+    }
+
+    for item in &items {
+        match item {
+            Item::Instruction(instr) => {
+                if targets.contains_key(&instr.address) {
+                    spc.dsp_tracker.reset();
+                    spc.block_cycles = 0;
+                }
+                spc.block_cycles += instr.definition.cycles as u32;
+                visit(item, spc)?;
+                update_state(instr, spc);
+            }
+            // Code after data or a gap starts a new block.
+            Item::Data { .. } | Item::Break => {
+                spc.dsp_tracker.reset();
+                spc.block_cycles = 0;
+                visit(item, spc)?;
+            }
+            Item::Unknown(_) => visit(item, spc)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Carries what is known about the machine state on to the next
+/// instruction in the listing.
+fn update_state(instr: &DecodedInstruction, spc: &mut Spc) {
+    match instr.definition.opcode {
+        0x20 => spc.direct_page = 0x0000,
+        0x40 => spc.direct_page = 0x0100,
+        _ => {}
+    }
+    if instr.flow() == FlowKind::Sequential {
+        spc.dsp_tracker.update(instr, spc.direct_page);
+    } else {
+        spc.dsp_tracker.reset();
+        spc.block_cycles = 0;
+    }
+}
+
+/// Writes the block comment and label line that go above `addr`.
+fn write_label(writer: &mut dyn Write, addr: u16, spc: &Spc) -> io::Result<()> {
+    for line in spc.comments.block(addr) {
+        if line.is_empty() {
+            writeln!(writer, ";")?;
+        } else {
+            writeln!(writer, "; {}", line)?;
+        }
+    }
+    match (spc.labels.get(addr), label_comment(addr, spc)) {
+        (Some(name), Some(comment)) => writeln!(writer, "{}: ; {}", name, comment),
+        (Some(name), None) => writeln!(writer, "{}:", name),
+        (None, Some(comment)) => writeln!(writer, "; {}", comment),
+        (None, None) => Ok(()),
+    }
+}
+
+/// The comment on the label line at `addr`: the label's own comment,
+/// followed by its cross-references when they are shown.
+pub fn label_comment(addr: u16, spc: &Spc) -> Option<String> {
+    let xrefs = spc
+        .config
+        .show_xrefs
+        .then(|| spc.xrefs.comment(addr))
+        .flatten();
+    match (spc.comments.label(addr), xrefs) {
+        (Some(comment), Some(xrefs)) => Some(format!("{}; {}", comment, xrefs)),
+        (Some(comment), None) => Some(comment.to_string()),
+        (None, xrefs) => xrefs,
+    }
+}
+
+fn collect_items(spc: &Spc, code: Option<&CodeMap>) -> Vec<Item> {
+    let mut items = Vec::new();
+    for (i, range) in spc.listing_ranges().into_iter().enumerate() {
+        if i > 0 {
+            items.push(Item::Break);
+        }
+        collect_range(spc, code, range, &mut items);
+    }
+    items
+}
+
+fn collect_range(spc: &Spc, code: Option<&CodeMap>, range: AddressRange, items: &mut Vec<Item>) {
+    let mut disasm = Disassembler::with_len(spc, range.start, range.size());
+    while disasm.remaining() > 0 {
+        let pc = disasm.pc();
+        let region = spc.data.region_at(pc);
+        let is_data = region.is_some() || code.is_some_and(|code| !code.is_code_start(pc));
+        if is_data {
+            let kind = region.map_or(DataKind::Bytes, |region| region.kind);
+            let mut len = data_run_len(spc, code, pc, disasm.remaining(), kind);
+            // A word cut short by the end of the run is listed as a byte.
+            let kind = match kind {
+                DataKind::Words | DataKind::Pointers if len < 2 => DataKind::Bytes,
+                DataKind::Words | DataKind::Pointers => {
+                    len &= !1;
+                    kind
+                }
+                _ => kind,
+            };
+            items.push(Item::Data {
+                addr: pc,
+                len,
+                kind,
+            });
+            disasm.seek(pc.wrapping_add(len));
+            continue;
+        }
+
+        match disasm.next() {
+            Some(Ok(instr)) => {
+                // An instruction that would run into a data region is
+                // listed as the bytes before the region.
+                let len = instr.definition.len as u16;
+                match spc.data.first_in(pc.wrapping_add(1), len - 1) {
+                    Some(offset) => {
+                        items.push(Item::Data {
+                            addr: pc,
+                            len: offset + 1,
+                            kind: DataKind::Bytes,
+                        });
+                        disasm.seek(pc.wrapping_add(offset + 1));
+                    }
+                    None => items.push(Item::Instruction(instr)),
+                }
+            }
+            Some(Err(e)) => items.push(Item::Unknown(e)),
+            None => break,
+        }
+    }
+}
+
+/// Number of data bytes to put on one line starting at `addr`. A line ends
+/// at a label or comment, where code starts or where the data region
+/// changes.
+fn data_run_len(
+    spc: &Spc,
+    code: Option<&CodeMap>,
+    addr: u16,
+    remaining: usize,
+    kind: DataKind,
+) -> u16 {
+    let region = spc.data.region_at(addr).copied();
+    let max = (kind.bytes_per_line() as usize).min(remaining) as u16;
+    let mut len = 1;
+    while len < max {
+        let next = addr.wrapping_add(len);
+        let same_region = spc.data.region_at(next).copied() == region;
+        let is_code = match region {
+            Some(_) => false,
+            None => code.is_none_or(|code| code.is_code_start(next)),
+        };
+        let is_marked = spc.labels.get(next).is_some() || spc.comments.contains(next);
+        if !same_region || is_code || is_marked {
+            break;
+        }
+        len += 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DataRegion;
+    use crate::spc::Config;
+
+    fn listing(spc: &mut Spc) -> Vec<String> {
+        let mut text = Vec::new();
+        write_listing(&mut text, spc, None).unwrap();
+        String::from_utf8(text)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn instructions_do_not_run_into_data() {
+        let code = [
+            0x00, // nop
+            0xE8, // mov a,#imm, cut short by the region
+            0x34, 0x12, // dw $1234
+        ];
+        for traverse in [false, true] {
+            let mut spc = Spc::new(Config::default(), 0x0800, 0x0804);
+            spc.load_rom(&code, 0x0800);
+            spc.data.insert(DataRegion {
+                start: 0x0802,
+                end: 0x0804,
+                kind: DataKind::Words,
+            });
+            let map = traverse.then(|| flow::trace(&spc, &[0x0800]));
+            let mut text = Vec::new();
+            write_listing(&mut text, &mut spc, map.as_ref()).unwrap();
+            assert_eq!(
+                String::from_utf8(text).unwrap(),
+                "0800: 00        nop\n\
+                 0801:           db    $e8\n\
+                 0802:           dw    $1234\n"
+            );
+        }
+    }
+
+    #[test]
+    fn block_cycles_restart_after_data() {
+        let code = [
+            0x00, // nop
+            0x00, // nop
+            0x12, 0x34, // data
+            0x00, // nop
+        ];
+        let config = Config {
+            show_hex: false,
+            show_cycles: true,
+            ..Config::default()
+        };
+        let mut spc = Spc::new(config, 0x0800, 0x0805);
+        spc.load_rom(&code, 0x0800);
+        spc.data.insert(DataRegion {
+            start: 0x0802,
+            end: 0x0804,
+            kind: DataKind::Bytes,
+        });
+        assert_eq!(
+            listing(&mut spc),
+            [
+                "0800: 2   [2]    nop",
+                "0801: 2   [4]    nop",
+                "0802:            db    $12,$34",
+                "0804: 2   [2]    nop",
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_inside_an_instruction_move_to_its_start() {
+        let code = [
+            0xE5, 0x34, 0x12, // mov a,$1234
+        ];
+        let config = Config {
+            show_hex: false,
+            ..Config::default()
+        };
+        let mut spc = Spc::new(config, 0x0800, 0x0803);
+        spc.load_rom(&code, 0x0800);
+        spc.comments.set_inline(0x0800, "load".to_string());
+        spc.comments.set_inline(0x0802, "high byte".to_string());
+        spc.comments.push_block(0x0801, "operand".to_string());
+        assert_eq!(
+            listing(&mut spc),
+            [
+                "; operand",
+                "0800: mov   a,$1234           ; load; high byte"
+            ]
+        );
+    }
+}
@@ -1,97 +1,275 @@
 mod cli;
-mod decoder;
-mod formatter;
-mod instruction;
-mod spc;
 
 use clap::Parser;
+use spcdas_rs::syntax::Dialect;
+use spcdas_rs::{
+    Cpu, comments, data, flow, ipl, listing, parse_addr, project, spc, spcfile, symbols, trace,
+    xrefs,
+};
 use std::fs;
 use std::io::{self, BufWriter, Write};
+use std::mem;
+use std::path::Path;
 use std::process;
 
 fn main() -> io::Result<()> {
-    let args = cli::Args::parse();
+    let mut args = cli::Args::parse();
 
-    let load_addr = cli::parse_hex(&args.load).unwrap_or_else(|e| {
+    let mut project = args.project.as_ref().map(|path| {
+        project::load(Path::new(path)).unwrap_or_else(|e| {
+            eprintln!("Error: Could not load project file '{}': {}", path, e);
+            process::exit(1);
+        })
+    });
+    if let Some(project) = &project {
+        cli::apply_project(&mut args, project);
+    }
+    let Some(input_file) = args.input_file.clone() else {
+        eprintln!("Error: No input file given on the command line or in the project file");
+        process::exit(1);
+    };
+    let Some(output_file) = args.output_file.clone() else {
+        eprintln!("Error: No output file given on the command line or in the project file");
+        process::exit(1);
+    };
+
+    let load = args.load.as_deref().unwrap_or("0000");
+    let load_addr = parse_addr(load).unwrap_or_else(|e| {
         eprintln!(
             "Error: Invalid hex value for --load argument '{}': {}",
-            args.load, e
+            load, e
         );
         process::exit(1);
     });
 
-    let start_pc = match &args.pc {
-        Some(pc_str) => cli::parse_hex(pc_str).unwrap_or_else(|e| {
-            eprintln!(
-                "Error: Invalid hex value for --pc argument '{}': {}",
-                pc_str, e
-            );
-            process::exit(1);
-        }),
-        None => load_addr,
-    };
-
-    let rom_bytes = match fs::read(&args.input_file) {
+    let rom_bytes = match fs::read(&input_file) {
         Ok(bytes) => bytes,
         Err(e) => {
-            eprintln!(
-                "Error: Could not read input file '{}': {}",
-                args.input_file, e
-            );
+            eprintln!("Error: Could not read input file '{}': {}", input_file, e);
             process::exit(1);
         }
     };
 
-    let stop_addr = if args.stop.eq_ignore_ascii_case("eof") {
-        load_addr.wrapping_add(rom_bytes.len() as u16)
+    let is_spc_file = !args.raw.unwrap_or_default() && spcfile::is_spc(&rom_bytes);
+    let image_len = if is_spc_file {
+        0x10000
     } else {
-        cli::parse_hex(&args.stop).unwrap_or_else(|e| {
+        rom_bytes.len()
+    };
+    let image_load = if is_spc_file { 0 } else { load_addr };
+
+    let stop = args.stop.as_deref().unwrap_or("eof");
+    let stop_addr = if stop.eq_ignore_ascii_case("eof") {
+        image_load.wrapping_add(image_len as u16)
+    } else {
+        parse_addr(stop).unwrap_or_else(|e| {
             eprintln!(
                 "Error: Invalid hex value for --stop argument '{}': {}",
-                args.stop, e
+                stop, e
             );
             process::exit(1);
         })
     };
 
+    let dialect = match &args.syntax {
+        Some(name) => name.parse().unwrap_or_else(|e| {
+            eprintln!("Error: Invalid --syntax argument: {}", e);
+            process::exit(1);
+        }),
+        None => Dialect::default(),
+    };
+
+    let format = match &args.format {
+        Some(name) => name.parse().unwrap_or_else(|e| {
+            eprintln!("Error: Invalid --format argument: {}", e);
+            process::exit(1);
+        }),
+        None => listing::Format::default(),
+    };
+
     let config = spc::Config {
-        show_addr: !args.no_addr,
-        show_hex: !args.no_hex,
-        resolve_rel: !args.no_rel_resolve,
+        show_addr: !args.no_addr.unwrap_or_default(),
+        show_hex: !args.no_hex.unwrap_or_default(),
+        resolve_rel: !args.no_rel_resolve.unwrap_or_default(),
+        gen_labels: args.labels.unwrap_or_default(),
+        hw_regs: args.hw_regs.unwrap_or_default(),
+        dsp_regs: args.dsp_regs.unwrap_or_default(),
+        // Traces already carry the running cycle count.
+        show_cycles: args.cycles.unwrap_or_default() && args.trace.is_none(),
+        dialect,
+        show_xrefs: args.xrefs.unwrap_or_default(),
     };
 
-    let mut spc = spc::Spc::new(config, start_pc, stop_addr);
-    spc.load_rom(&rom_bytes, load_addr);
+    let mut spc = spc::Spc::new(config, image_load, stop_addr);
+    // CONTROL as saved in an .spc snapshot, for `--ipl auto`.
+    let mut control = None;
+    if is_spc_file {
+        if let Err(e) = spcfile::load(&mut spc, &rom_bytes) {
+            eprintln!("Error: Could not load SPC file '{}': {}", input_file, e);
+            process::exit(1);
+        }
+        control = Some(spc.mem[0xF1]);
+        spc.pc = spc.regs.pc;
+        if spc.regs.psw & 0x20 != 0 {
+            spc.direct_page = 0x0100;
+        }
+    } else {
+        spc.load_rom(&rom_bytes, load_addr);
+    }
 
-    let mut writer: Box<dyn Write> = match fs::File::create(&args.output_file) {
+    if let Some(project) = &project {
+        for input in project.inputs.iter().skip(1) {
+            let bytes = fs::read(&input.path).unwrap_or_else(|e| {
+                eprintln!(
+                    "Error: Could not read input file '{}': {}",
+                    input.path.display(),
+                    e
+                );
+                process::exit(1);
+            });
+            spc.load_rom(&bytes, input.load.unwrap_or(0));
+        }
+    }
+
+    if let Some(pc_str) = &args.pc {
+        spc.pc = parse_addr(pc_str).unwrap_or_else(|e| {
+            eprintln!(
+                "Error: Invalid hex value for --pc argument '{}': {}",
+                pc_str, e
+            );
+            process::exit(1);
+        });
+    }
+
+    if let Some(mode) = &args.ipl {
+        let mode: ipl::IplMode = mode.parse().unwrap_or_else(|e| {
+            eprintln!("Error: Invalid --ipl argument: {}", e);
+            process::exit(1);
+        });
+        if mode.rom_enabled(control) {
+            ipl::map_rom(&mut spc);
+        }
+    }
+
+    for path in &args.symbols {
+        let text = fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Error: Could not read symbol file '{}': {}", path, e);
+            process::exit(1);
+        });
+        let symbols = symbols::parse(&text).unwrap_or_else(|e| {
+            eprintln!("Error: Invalid symbol file '{}': {}", path, e);
+            process::exit(1);
+        });
+        symbols::apply(&symbols, &mut spc.labels, &mut spc.comments);
+    }
+
+    // User symbols take precedence over the ROM's names.
+    if spc.ipl_rom_mapped {
+        ipl::add_labels(&mut spc.labels);
+        spc.data.insert(ipl::reset_vector());
+    }
+
+    if let Some(project) = &mut project {
+        for region in &project.data {
+            spc.data.insert(*region);
+        }
+        spc.comments.merge(mem::take(&mut project.comments));
+    }
+    for path in &args.comments {
+        let text = fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Error: Could not read comments file '{}': {}", path, e);
+            process::exit(1);
+        });
+        let comments = comments::parse(&text).unwrap_or_else(|e| {
+            eprintln!("Error: Invalid comments file '{}': {}", path, e);
+            process::exit(1);
+        });
+        spc.comments.merge(comments);
+    }
+    for spec in &args.range {
+        let range = spec.parse().unwrap_or_else(|e| {
+            eprintln!("Error: Invalid --range '{}': {}", spec, e);
+            process::exit(1);
+        });
+        spc.ranges.push(range);
+    }
+    for spec in &args.data {
+        let region = spec.parse().unwrap_or_else(|e| {
+            eprintln!("Error: Invalid --data region '{}': {}", spec, e);
+            process::exit(1);
+        });
+        spc.data.insert(region);
+    }
+    for spec in &args.jump_table {
+        let region = data::parse_jump_table(spec).unwrap_or_else(|e| {
+            eprintln!("Error: Invalid --jump-table '{}': {}", spec, e);
+            process::exit(1);
+        });
+        spc.data.insert(region);
+    }
+
+    let mut writer: Box<dyn Write> = match fs::File::create(&output_file) {
         Ok(file) => Box::new(BufWriter::new(file)),
         Err(e) => {
             eprintln!(
                 "Error: Could not create output file '{}': {}",
-                args.output_file, e
+                output_file, e
             );
             process::exit(1);
         }
     };
 
-    while spc.pc.wrapping_sub(spc.stop) != 0 {
-        let pc = spc.pc;
-        match decoder::decode_one(&spc, pc) {
-            Ok(instr) => {
-                let line = formatter::format_instruction(&instr, &spc);
-                writeln!(writer, "{}", line)?;
-                spc.pc = spc.pc.wrapping_add(instr.definition.len as u16);
-            }
-            Err(e) => {
-                writeln!(
-                    writer,
-                    "{:04x}: db {:02x}    ; unknown opcode",
-                    e.pc, e.opcode
-                )?;
-                spc.pc = spc.pc.wrapping_add(1);
-            }
+    if let Some(count) = args.trace {
+        let limit = if args.trace_cycles {
+            trace::TraceLimit::Cycles(count)
+        } else {
+            trace::TraceLimit::Instructions(count)
+        };
+        let mut cpu = Cpu::new(&spc);
+        return trace::write_trace(&mut writer, &mut spc, &mut cpu, limit);
+    }
+
+    let code_map = args.traverse.unwrap_or_default().then(|| {
+        let entries: Vec<u16> = if !args.entry.is_empty() {
+            args.entry
+                .iter()
+                .map(|entry| {
+                    parse_addr(entry).unwrap_or_else(|e| {
+                        eprintln!(
+                            "Error: Invalid hex value for --entry argument '{}': {}",
+                            entry, e
+                        );
+                        process::exit(1);
+                    })
+                })
+                .collect()
+        } else if !spc.ranges.is_empty() {
+            spc.ranges.iter().map(|range| range.start).collect()
+        } else {
+            vec![spc.pc]
+        };
+        flow::trace(&spc, &entries)
+    });
+    if let Some(code_map) = &code_map {
+        for table in code_map.jump_tables() {
+            spc.data.insert(*table);
         }
     }
 
+    listing::write(&mut writer, &mut spc, code_map.as_ref(), format)?;
+
+    if let Some(report_file) = &args.xref_report {
+        let mut report = match fs::File::create(report_file) {
+            Ok(file) => BufWriter::new(file),
+            Err(e) => {
+                eprintln!(
+                    "Error: Could not create xref report '{}': {}",
+                    report_file, e
+                );
+                process::exit(1);
+            }
+        };
+        xrefs::write_report(&mut report, &spc)?;
+    }
     Ok(())
 }
@@ -0,0 +1,101 @@
+use crate::spc::Spc;
+use std::collections::BTreeMap;
+
+/// Read access to the 64 KiB SPC700 address space.
+pub trait Memory {
+    /// Returns the byte at `addr`, or `None` if its value is unknown.
+    fn read(&self, addr: u16) -> Option<u8>;
+
+    fn read_word(&self, addr: u16) -> Option<u16> {
+        let lo = self.read(addr)?;
+        let hi = self.read(addr.wrapping_add(1))?;
+        Some(u16::from_le_bytes([lo, hi]))
+    }
+}
+
+impl Memory for Spc {
+    fn read(&self, addr: u16) -> Option<u8> {
+        Some(self.read_byte(addr))
+    }
+}
+
+/// A slice indexed directly by address; bytes past its end are unknown.
+impl Memory for [u8] {
+    fn read(&self, addr: u16) -> Option<u8> {
+        self.get(addr as usize).copied()
+    }
+}
+
+impl<const N: usize> Memory for [u8; N] {
+    fn read(&self, addr: u16) -> Option<u8> {
+        self.get(addr as usize).copied()
+    }
+}
+
+impl<M: Memory + ?Sized> Memory for &M {
+    fn read(&self, addr: u16) -> Option<u8> {
+        (**self).read(addr)
+    }
+}
+
+/// A slice mapped at `base`, e.g. a code fragment loaded at $0800.
+#[derive(Debug, Clone, Copy)]
+pub struct SliceMemory<'a> {
+    pub base: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> SliceMemory<'a> {
+    pub fn new(data: &'a [u8], base: u16) -> Self {
+        SliceMemory { base, data }
+    }
+}
+
+impl Memory for SliceMemory<'_> {
+    fn read(&self, addr: u16) -> Option<u8> {
+        self.data
+            .get(addr.wrapping_sub(self.base) as usize)
+            .copied()
+    }
+}
+
+/// Memory where only explicitly written bytes are known.
+#[derive(Debug, Clone, Default)]
+pub struct SparseMemory {
+    bytes: BTreeMap<u16, u8>,
+}
+
+impl SparseMemory {
+    pub fn new() -> Self {
+        SparseMemory::default()
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        self.bytes.insert(addr, value);
+    }
+
+    pub fn load(&mut self, data: &[u8], addr: u16) {
+        for (i, &byte) in data.iter().enumerate() {
+            self.write(addr.wrapping_add(i as u16), byte);
+        }
+    }
+}
+
+impl Memory for SparseMemory {
+    fn read(&self, addr: u16) -> Option<u8> {
+        self.bytes.get(&addr).copied()
+    }
+}
+
+/// Reads from `top` where it is known and falls back to `bottom`.
+#[derive(Debug, Clone, Copy)]
+pub struct Overlay<T, B> {
+    pub top: T,
+    pub bottom: B,
+}
+
+impl<T: Memory, B: Memory> Memory for Overlay<T, B> {
+    fn read(&self, addr: u16) -> Option<u8> {
+        self.top.read(addr).or_else(|| self.bottom.read(addr))
+    }
+}
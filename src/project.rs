@@ -0,0 +1,439 @@
+use crate::comments::Comments;
+use crate::data::{DataKind, DataRegion};
+use crate::ipl::IplMode;
+use crate::listing::Format;
+use crate::spc::AddressRange;
+use crate::syntax::Dialect;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A disassembly job read from a TOML project file.
+///
+/// ```toml
+/// output = "driver.asm"
+/// xref_report = "driver.xref"
+/// pc = "0800"
+/// entries = ["0800", "1200"]
+/// symbols = ["driver.sym"]
+/// comment_files = ["driver-notes.txt"]
+///
+/// [[input]]
+/// file = "driver.spc"
+///
+/// [[range]]
+/// start = "0400"
+/// end = "0a00"
+///
+/// [[data]]
+/// start = "1000"
+/// end = "1040"
+/// kind = "ptr"
+///
+/// [[jump_table]]
+/// addr = "0a40"
+/// count = 12
+///
+/// [comments]
+/// "0804" = "out-of-range commands end the track"
+///
+/// [block_comments]
+/// "0800" = """
+/// Sequence command dispatcher
+/// """
+///
+/// [options]
+/// traverse = true
+/// labels = true
+/// ```
+///
+/// Addresses are hex strings (`"0800"`, `"$0800"`) or integers. Relative
+/// paths are taken from the project file's directory.
+#[derive(Debug, Default)]
+pub struct Project {
+    pub inputs: Vec<ProjectInput>,
+    pub output: Option<PathBuf>,
+    /// Where to write the cross-reference report.
+    pub xref_report: Option<PathBuf>,
+    pub pc: Option<u16>,
+    pub stop: Option<u16>,
+    /// Ranges to list in place of `pc..stop`.
+    pub ranges: Vec<AddressRange>,
+    pub entries: Vec<u16>,
+    pub symbols: Vec<PathBuf>,
+    /// Data regions and jump tables.
+    pub data: Vec<DataRegion>,
+    /// End-of-line comments from `[comments]` and block comments from
+    /// `[block_comments]`.
+    pub comments: Comments,
+    pub comment_files: Vec<PathBuf>,
+    pub options: ProjectOptions,
+}
+
+/// An image to load. The first input may be an `.spc` snapshot; the rest
+/// are raw binaries copied in at their load addresses.
+#[derive(Debug)]
+pub struct ProjectInput {
+    pub path: PathBuf,
+    pub load: Option<u16>,
+    pub raw: bool,
+}
+
+/// Output switches, named after the matching command-line flags.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectOptions {
+    /// `"on"`, `"off"` or `"auto"`.
+    pub ipl: Option<IplMode>,
+    pub syntax: Option<Dialect>,
+    pub format: Option<Format>,
+    pub traverse: bool,
+    pub labels: bool,
+    pub hw_regs: bool,
+    pub dsp_regs: bool,
+    pub cycles: bool,
+    pub xrefs: bool,
+    pub no_addr: bool,
+    pub no_hex: bool,
+    pub no_rel_resolve: bool,
+}
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectError::Io(e) => write!(f, "{}", e),
+            ProjectError::Toml(e) => write!(f, "{}", e),
+            ProjectError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProject {
+    #[serde(default)]
+    input: Vec<RawInput>,
+    output: Option<String>,
+    xref_report: Option<String>,
+    pc: Option<RawAddr>,
+    stop: Option<RawAddr>,
+    #[serde(default)]
+    range: Vec<RawRange>,
+    #[serde(default)]
+    entries: Vec<RawAddr>,
+    #[serde(default)]
+    symbols: Vec<String>,
+    #[serde(default)]
+    data: Vec<RawData>,
+    #[serde(default)]
+    jump_table: Vec<RawJumpTable>,
+    #[serde(default)]
+    comment_files: Vec<String>,
+    #[serde(default)]
+    comments: BTreeMap<String, String>,
+    #[serde(default)]
+    block_comments: BTreeMap<String, String>,
+    #[serde(default)]
+    options: ProjectOptions,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawInput {
+    file: String,
+    load: Option<RawAddr>,
+    #[serde(default)]
+    raw: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawData {
+    start: RawAddr,
+    end: RawAddr,
+    kind: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRange {
+    start: RawAddr,
+    end: RawAddr,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawJumpTable {
+    addr: RawAddr,
+    count: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawAddr {
+    Int(u16),
+    Hex(String),
+}
+
+impl RawAddr {
+    fn resolve(&self) -> Result<u16, ProjectError> {
+        match self {
+            RawAddr::Int(addr) => Ok(*addr),
+            RawAddr::Hex(s) => parse_addr(s),
+        }
+    }
+}
+
+fn parse_addr(s: &str) -> Result<u16, ProjectError> {
+    crate::parse_addr(s)
+        .map_err(|e| ProjectError::Invalid(format!("invalid address '{}': {}", s, e)))
+}
+
+/// Reads and parses the project file at `path`.
+pub fn load(path: &Path) -> Result<Project, ProjectError> {
+    let text = fs::read_to_string(path).map_err(ProjectError::Io)?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    parse(&text, base_dir)
+}
+
+/// Parses project file text, resolving relative paths against `base_dir`.
+pub fn parse(text: &str, base_dir: &Path) -> Result<Project, ProjectError> {
+    let raw: RawProject = toml::from_str(text).map_err(ProjectError::Toml)?;
+    let path = |p: &str| base_dir.join(p);
+
+    let inputs = raw
+        .input
+        .iter()
+        .map(|input| {
+            Ok(ProjectInput {
+                path: path(&input.file),
+                load: input.load.as_ref().map(RawAddr::resolve).transpose()?,
+                raw: input.raw,
+            })
+        })
+        .collect::<Result<_, ProjectError>>()?;
+
+    let mut data = Vec::new();
+    for region in &raw.data {
+        let kind = match &region.kind {
+            Some(kind) => kind.parse().map_err(ProjectError::Invalid)?,
+            None => DataKind::Bytes,
+        };
+        data.push(DataRegion {
+            start: region.start.resolve()?,
+            end: region.end.resolve()?,
+            kind,
+        });
+    }
+    for table in &raw.jump_table {
+        if !(1..=0x8000).contains(&table.count) {
+            return Err(ProjectError::Invalid(format!(
+                "invalid jump table entry count {}",
+                table.count
+            )));
+        }
+        data.push(DataRegion::jump_table(table.addr.resolve()?, table.count));
+    }
+
+    let mut comments = Comments::default();
+    for (addr, comment) in &raw.comments {
+        comments.set_inline(parse_addr(addr)?, comment.clone());
+    }
+    for (addr, text) in &raw.block_comments {
+        let addr = parse_addr(addr)?;
+        for line in text.trim_end().lines() {
+            comments.push_block(addr, line.trim_end().to_string());
+        }
+    }
+
+    Ok(Project {
+        inputs,
+        output: raw.output.as_deref().map(path),
+        xref_report: raw.xref_report.as_deref().map(path),
+        pc: raw.pc.as_ref().map(RawAddr::resolve).transpose()?,
+        stop: raw.stop.as_ref().map(RawAddr::resolve).transpose()?,
+        ranges: raw
+            .range
+            .iter()
+            .map(|range| {
+                Ok(AddressRange {
+                    start: range.start.resolve()?,
+                    end: range.end.resolve()?,
+                })
+            })
+            .collect::<Result<_, ProjectError>>()?,
+        entries: raw
+            .entries
+            .iter()
+            .map(RawAddr::resolve)
+            .collect::<Result<_, _>>()?,
+        symbols: raw.symbols.iter().map(|s| path(s)).collect(),
+        data,
+        comments,
+        comment_files: raw.comment_files.iter().map(|s| path(s)).collect(),
+        options: raw.options,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL: &str = r#"
+output = "driver.asm"
+xref_report = "driver.xref"
+pc = "$0800"
+stop = 4096
+entries = ["0800", "0x1200"]
+symbols = ["driver.sym"]
+comment_files = ["notes.txt"]
+
+[[input]]
+file = "driver.spc"
+
+[[input]]
+file = "patch.bin"
+load = "1200"
+raw = true
+
+[[range]]
+start = "0400"
+end = "0a00"
+
+[[data]]
+start = "1000"
+end = "1040"
+kind = "ptr"
+
+[[data]]
+start = "1040"
+end = "1044"
+
+[[jump_table]]
+addr = "0a40"
+count = 12
+
+[comments]
+"0804" = "out of range"
+
+[block_comments]
+"0800" = """
+Dispatcher
+
+Commands index the table.
+"""
+
+[options]
+ipl = "auto"
+syntax = "wla-dx"
+format = "html"
+traverse = true
+labels = true
+no_hex = true
+"#;
+
+    #[test]
+    fn parses_every_section() {
+        let project = parse(FULL, Path::new("jobs")).unwrap();
+        let inputs: Vec<_> = project
+            .inputs
+            .iter()
+            .map(|input| (input.path.clone(), input.load, input.raw))
+            .collect();
+        assert_eq!(
+            inputs,
+            [
+                (PathBuf::from("jobs/driver.spc"), None, false),
+                (PathBuf::from("jobs/patch.bin"), Some(0x1200), true),
+            ]
+        );
+        assert_eq!(project.output, Some(PathBuf::from("jobs/driver.asm")));
+        assert_eq!(project.xref_report, Some(PathBuf::from("jobs/driver.xref")));
+        assert_eq!((project.pc, project.stop), (Some(0x0800), Some(0x1000)));
+        assert_eq!(
+            project.ranges,
+            [AddressRange {
+                start: 0x0400,
+                end: 0x0A00
+            }]
+        );
+        assert_eq!(project.entries, [0x0800, 0x1200]);
+        assert_eq!(project.symbols, [PathBuf::from("jobs/driver.sym")]);
+        assert_eq!(project.comment_files, [PathBuf::from("jobs/notes.txt")]);
+        assert_eq!(
+            project.data,
+            [
+                DataRegion {
+                    start: 0x1000,
+                    end: 0x1040,
+                    kind: DataKind::Pointers
+                },
+                DataRegion {
+                    start: 0x1040,
+                    end: 0x1044,
+                    kind: DataKind::Bytes
+                },
+                DataRegion::jump_table(0x0A40, 12),
+            ]
+        );
+        assert_eq!(project.comments.inline(0x0804), Some("out of range"));
+        assert_eq!(
+            project.comments.block(0x0800),
+            ["Dispatcher", "", "Commands index the table."]
+        );
+
+        let options = project.options;
+        assert_eq!(options.ipl, Some(IplMode::Auto));
+        assert_eq!(options.syntax, Some(Dialect::Wla));
+        assert_eq!(options.format, Some(Format::Html));
+        assert!(options.traverse && options.labels && options.no_hex);
+        assert!(!options.cycles && !options.xrefs);
+    }
+
+    #[test]
+    fn empty_project_uses_defaults() {
+        let project = parse("", Path::new("")).unwrap();
+        assert!(project.inputs.is_empty() && project.output.is_none());
+        assert!(project.ranges.is_empty() && project.data.is_empty());
+        assert!(project.options.syntax.is_none() && !project.options.traverse);
+    }
+
+    #[test]
+    fn rejects_bad_projects() {
+        let invalid = |text: &str| match parse(text, Path::new("")) {
+            Err(ProjectError::Invalid(message)) => message,
+            other => panic!("expected an invalid project, got {:?}", other),
+        };
+        assert!(invalid("pc = \"08zz\"").contains("invalid address '08zz'"));
+        assert!(invalid("[[data]]\nstart = 0\nend = 4\nkind = \"float\"").contains("float"));
+        assert!(invalid("[[jump_table]]\naddr = 0\ncount = 0").contains("count"));
+        assert!(invalid("[comments]\n\"xyz\" = \"x\"").contains("xyz"));
+
+        for text in [
+            "pc = ",
+            "colour = \"red\"",
+            "[options]\ncolor = true",
+            "[options]\nsyntax = \"nasm\"",
+            "[[input]]\nload = \"0800\"",
+            "pc = 70000",
+        ] {
+            assert!(
+                matches!(parse(text, Path::new("")), Err(ProjectError::Toml(_))),
+                "{}",
+                text
+            );
+        }
+    }
+}
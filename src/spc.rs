@@ -1,8 +1,25 @@
+use crate::comments::Comments;
+use crate::data::DataMap;
+use crate::dsp::DspTracker;
+use crate::labels::Labels;
+use crate::parse_addr;
+use crate::syntax::Dialect;
+use crate::xrefs::Xrefs;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
     pub show_addr: bool,
     pub show_hex: bool,
     pub resolve_rel: bool,
+    pub gen_labels: bool,
+    pub hw_regs: bool,
+    pub dsp_regs: bool,
+    pub show_cycles: bool,
+    pub dialect: Dialect,
+    /// Put `; xref:` comments on label lines.
+    pub show_xrefs: bool,
 }
 
 impl Default for Config {
@@ -11,28 +28,160 @@ impl Default for Config {
             show_addr: true,
             show_hex: true,
             resolve_rel: true,
+            gen_labels: false,
+            hw_regs: false,
+            dsp_regs: false,
+            show_cycles: false,
+            dialect: Dialect::Spcdas,
+            show_xrefs: false,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Registers {
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub psw: u8,
+    pub sp: u8,
+}
+
+/// A range of addresses to list, `start` up to but not including `end`.
+/// Wraps past $FFFF; `end == start` covers the whole address space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl AddressRange {
+    /// Number of bytes in the range.
+    pub fn size(&self) -> usize {
+        match self.end.wrapping_sub(self.start) {
+            0 => 0x10000,
+            len => len as usize,
+        }
+    }
+}
+
+impl FromStr for AddressRange {
+    type Err = String;
+
+    /// Parses `START:END` with hex addresses.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((start, end)) = s.split_once(':') else {
+            return Err(format!("expected START:END but found '{}'", s));
+        };
+        let addr =
+            |part: &str| parse_addr(part).map_err(|e| format!("invalid address '{}': {}", part, e));
+        Ok(AddressRange {
+            start: addr(start)?,
+            end: addr(end)?,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct Spc {
     pub mem: [u8; 65536],
+    pub dsp: [u8; 128],
+    /// RAM beneath the IPL ROM at $FFC0-$FFFF while the ROM is mapped;
+    /// `mem` holds it otherwise.
+    pub ipl_ram: [u8; 64],
+    /// Whether `mem` currently shows the IPL ROM at $FFC0-$FFFF rather than
+    /// the RAM in `ipl_ram`.
+    pub ipl_rom_mapped: bool,
+    pub regs: Registers,
     pub pc: u16,
+    /// End of the listing; the whole address space from `pc` when equal
+    /// to it.
     pub stop: u16,
+    /// Ranges to list instead of `pc..stop` when not empty.
+    pub ranges: Vec<AddressRange>,
+    /// $0000 or $0100, following the P flag as `setp`/`clrp` are listed.
+    pub direct_page: u16,
+    pub dsp_tracker: DspTracker,
+    /// Base cycles of the current basic block up to and including the
+    /// instruction being listed.
+    pub block_cycles: u32,
     pub config: Config,
+    pub labels: Labels,
+    pub comments: Comments,
+    pub data: DataMap,
+    /// References made by the code in the listing, filled in as it is
+    /// written.
+    pub xrefs: Xrefs,
+    /// Addresses of the instruction and data lines in the listing, filled
+    /// in before it is written.
+    pub lines: BTreeSet<u16>,
 }
 
 impl Spc {
     pub fn new(config: Config, start_pc: u16, stop_addr: u16) -> Self {
         Spc {
             mem: [0xFF; 65536],
+            dsp: [0; 128],
+            ipl_ram: [0xFF; 64],
+            ipl_rom_mapped: false,
+            regs: Registers::default(),
             pc: start_pc,
             stop: stop_addr,
+            ranges: Vec::new(),
+            direct_page: 0,
+            dsp_tracker: DspTracker::default(),
+            block_cycles: 0,
             config,
+            labels: Labels::default(),
+            comments: Comments::default(),
+            data: DataMap::default(),
+            xrefs: Xrefs::default(),
+            lines: BTreeSet::new(),
         }
     }
 
+    /// The ranges a listing covers, in address order with overlapping and
+    /// adjacent ranges joined, including a range that wraps past $FFFF with
+    /// those at the bottom of memory: `ranges`, or `pc..stop` when there
+    /// are none. `pc == stop` lists the whole address space.
+    pub fn listing_ranges(&self) -> Vec<AddressRange> {
+        if self.ranges.is_empty() {
+            return vec![AddressRange {
+                start: self.pc,
+                end: self.stop,
+            }];
+        }
+        let mut ranges = self.ranges.clone();
+        ranges.sort_by_key(|range| range.start);
+        let mut spans: Vec<(usize, usize)> = Vec::new();
+        for range in ranges {
+            let start = range.start as usize;
+            let end = start + range.size();
+            match spans.last_mut() {
+                Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+                _ => spans.push((start, end)),
+            }
+        }
+        // The last span may run past $FFFF into the first ones.
+        while spans.len() > 1 {
+            let (first_start, first_end) = spans[0];
+            let last_end = &mut spans.last_mut().unwrap().1;
+            if first_start + 0x10000 > *last_end {
+                break;
+            }
+            *last_end = (*last_end).max(first_end + 0x10000);
+            spans.remove(0);
+        }
+        spans
+            .into_iter()
+            .map(|(start, end)| AddressRange {
+                start: start as u16,
+                end: end.min(start + 0x10000) as u16,
+            })
+            .collect()
+    }
+
     pub fn load_rom(&mut self, data: &[u8], load_addr: u16) {
         let start = load_addr as usize;
         if start >= self.mem.len() {
@@ -53,3 +202,64 @@ impl Spc {
         u16::from_le_bytes([lo, hi])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(spans: &[(u16, u16)]) -> Vec<(u16, u16)> {
+        let mut spc = Spc::new(Config::default(), 0, 0);
+        spc.ranges = spans
+            .iter()
+            .map(|&(start, end)| AddressRange { start, end })
+            .collect();
+        spc.listing_ranges()
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn listing_ranges_join_overlapping_and_adjacent_ranges() {
+        assert_eq!(
+            ranges(&[(0x0900, 0x0a00), (0x0800, 0x0880), (0x0870, 0x0900)]),
+            [(0x0800, 0x0a00)]
+        );
+        assert_eq!(
+            ranges(&[(0x0880, 0x0900), (0x0800, 0x0880)]),
+            [(0x0800, 0x0900)]
+        );
+        assert_eq!(
+            ranges(&[(0x0800, 0x0880), (0x0881, 0x0900)]),
+            [(0x0800, 0x0880), (0x0881, 0x0900)]
+        );
+    }
+
+    #[test]
+    fn listing_ranges_join_across_the_top_of_memory() {
+        assert_eq!(
+            ranges(&[(0x0000, 0x0040), (0xffc0, 0x0000)]),
+            [(0xffc0, 0x0040)]
+        );
+        assert_eq!(
+            ranges(&[(0x0000, 0x0040), (0x0030, 0x0080), (0xff00, 0x0020)]),
+            [(0xff00, 0x0080)]
+        );
+        assert_eq!(
+            ranges(&[(0x0041, 0x0080), (0xffc0, 0x0040)]),
+            [(0x0041, 0x0080), (0xffc0, 0x0040)]
+        );
+        assert_eq!(
+            ranges(&[(0x0000, 0x8000), (0x8000, 0x0000)]),
+            [(0x0000, 0x0000)]
+        );
+    }
+
+    #[test]
+    fn parses_ranges_with_any_hex_prefix() {
+        let range: AddressRange = "$0800:0x0900".parse().unwrap();
+        assert_eq!((range.start, range.end), (0x0800, 0x0900));
+        assert!("0800".parse::<AddressRange>().is_err());
+        assert!("0800:zz".parse::<AddressRange>().is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::spc::{Registers, Spc};
+use std::fmt;
+
+pub const SIGNATURE: &[u8] = b"SNES-SPC700 Sound File Data";
+
+const PC_OFFSET: usize = 0x25;
+const A_OFFSET: usize = 0x27;
+const X_OFFSET: usize = 0x28;
+const Y_OFFSET: usize = 0x29;
+const PSW_OFFSET: usize = 0x2A;
+const SP_OFFSET: usize = 0x2B;
+pub const RAM_OFFSET: usize = 0x100;
+const DSP_OFFSET: usize = 0x10100;
+const IPL_RAM_OFFSET: usize = 0x101C0;
+const MIN_LEN: usize = DSP_OFFSET + 128;
+
+#[derive(Debug)]
+pub enum SpcFileError {
+    BadSignature,
+    Truncated { len: usize },
+}
+
+impl fmt::Display for SpcFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpcFileError::BadSignature => write!(f, "missing SNES-SPC700 file signature"),
+            SpcFileError::Truncated { len } => write!(
+                f,
+                "file is {} bytes, expected at least {} bytes",
+                len, MIN_LEN
+            ),
+        }
+    }
+}
+
+pub fn is_spc(data: &[u8]) -> bool {
+    data.starts_with(SIGNATURE)
+}
+
+/// Loads an `.spc` snapshot: the 64 KiB RAM image, the CPU registers and
+/// the DSP register block. When CONTROL ($F1) bit 7 is set the image may
+/// hold the IPL ROM at $FFC0, so the RAM beneath it is taken from the extra
+/// RAM block if the file has one.
+pub fn load(spc: &mut Spc, data: &[u8]) -> Result<(), SpcFileError> {
+    if !is_spc(data) {
+        return Err(SpcFileError::BadSignature);
+    }
+    if data.len() < MIN_LEN {
+        return Err(SpcFileError::Truncated { len: data.len() });
+    }
+
+    spc.regs = Registers {
+        pc: u16::from_le_bytes([data[PC_OFFSET], data[PC_OFFSET + 1]]),
+        a: data[A_OFFSET],
+        x: data[X_OFFSET],
+        y: data[Y_OFFSET],
+        psw: data[PSW_OFFSET],
+        sp: data[SP_OFFSET],
+    };
+    spc.mem.copy_from_slice(&data[RAM_OFFSET..DSP_OFFSET]);
+    spc.dsp.copy_from_slice(&data[DSP_OFFSET..MIN_LEN]);
+    if spc.mem[0xF1] & 0x80 != 0
+        && let Some(ipl_ram) = data.get(IPL_RAM_OFFSET..IPL_RAM_OFFSET + 64)
+    {
+        spc.mem[0xFFC0..].copy_from_slice(ipl_ram);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipl;
+    use crate::spc::Config;
+
+    /// A minimal snapshot without the extra RAM block.
+    fn image() -> Vec<u8> {
+        let mut data = vec![0; MIN_LEN];
+        data[..SIGNATURE.len()].copy_from_slice(SIGNATURE);
+        data[PC_OFFSET..PC_OFFSET + 2].copy_from_slice(&[0x34, 0x12]);
+        data[A_OFFSET] = 0x01;
+        data[X_OFFSET] = 0x02;
+        data[Y_OFFSET] = 0x03;
+        data[PSW_OFFSET] = 0x20;
+        data[SP_OFFSET] = 0xEF;
+        data[RAM_OFFSET] = 0x11;
+        data[RAM_OFFSET + 0x0800] = 0xE8;
+        data[RAM_OFFSET + 0xFFFF] = 0x5A;
+        data[DSP_OFFSET + 0x6C] = 0xE0;
+        data
+    }
+
+    fn spc() -> Spc {
+        Spc::new(Config::default(), 0, 0)
+    }
+
+    #[test]
+    fn loads_registers_ram_and_dsp() {
+        let mut spc = spc();
+        load(&mut spc, &image()).unwrap();
+        let regs = &spc.regs;
+        assert_eq!(regs.pc, 0x1234);
+        assert_eq!((regs.a, regs.x, regs.y), (0x01, 0x02, 0x03));
+        assert_eq!((regs.psw, regs.sp), (0x20, 0xEF));
+        assert_eq!(spc.mem[0x0000], 0x11);
+        assert_eq!(spc.mem[0x0800], 0xE8);
+        assert_eq!(spc.mem[0xFFFF], 0x5A);
+        assert_eq!(spc.dsp[0x6C], 0xE0);
+        assert!(!spc.ipl_rom_mapped);
+    }
+
+    #[test]
+    fn loads_extra_ram_beneath_an_enabled_rom() {
+        let mut data = image();
+        data.resize(IPL_RAM_OFFSET + 64, 0);
+        data[IPL_RAM_OFFSET] = 0x42;
+        data[RAM_OFFSET + 0xFFC0..DSP_OFFSET].copy_from_slice(&ipl::IPL_ROM);
+        let mut spc = spc();
+        load(&mut spc, &data).unwrap();
+        assert_eq!(spc.mem[0xFFC0], 0xCD);
+
+        data[RAM_OFFSET + 0xF1] = 0x80;
+        load(&mut spc, &data).unwrap();
+        assert_eq!(spc.mem[0xFFC0], 0x42);
+        ipl::map_rom(&mut spc);
+        assert_eq!(spc.mem[0xFFC0..], ipl::IPL_ROM);
+        ipl::unmap_rom(&mut spc);
+        assert_eq!(spc.mem[0xFFC0], 0x42);
+        assert_eq!(spc.mem[0xFFC1..], data[IPL_RAM_OFFSET + 1..]);
+    }
+
+    #[test]
+    fn rejects_truncated_and_unsigned_files() {
+        let mut data = image();
+        data.truncate(MIN_LEN - 1);
+        assert!(matches!(
+            load(&mut spc(), &data),
+            Err(SpcFileError::Truncated { len }) if len == MIN_LEN - 1
+        ));
+        assert!(matches!(
+            load(&mut spc(), &data[..SIGNATURE.len()]),
+            Err(SpcFileError::Truncated { .. })
+        ));
+
+        let mut data = image();
+        data[0] = b'X';
+        assert!(matches!(
+            load(&mut spc(), &data),
+            Err(SpcFileError::BadSignature)
+        ));
+    }
+}
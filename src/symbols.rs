@@ -0,0 +1,251 @@
+use crate::comments::Comments;
+use crate::ipl::{IPL_ADDR, IPL_ROM};
+use crate::labels::Labels;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub addr: u16,
+    pub name: String,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct SymbolError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Labels,
+    Comments,
+    Other,
+}
+
+/// Parses a symbol file. Three layouts are accepted, and may be mixed:
+///
+/// * plain `ADDR NAME [comment]` lines, e.g. `0800 play_note ; entry`
+/// * bsnes-plus / WLA-DX `.sym` files with `[labels]` (`00:0800 name`) and
+///   `[comments]` sections
+/// * Mesen `.mlb` lines such as `SpcRam:0800:name:comment`; `SpcRom`
+///   offsets are into the IPL ROM at $FFC0
+pub fn parse(text: &str) -> Result<Vec<Symbol>, SymbolError> {
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let mut section = Section::Labels;
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line_no = index + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = match &line[1..line.len() - 1] {
+                "labels" => Section::Labels,
+                "comments" => Section::Comments,
+                _ => Section::Other,
+            };
+            continue;
+        }
+        if section == Section::Other {
+            continue;
+        }
+
+        let err = |message: &str| SymbolError {
+            line: line_no,
+            message: format!("{} in '{}'", message, line),
+        };
+
+        if is_other_mesen(line) {
+            continue;
+        }
+        if let Some(symbol) = parse_mesen(line) {
+            symbols.push(symbol.map_err(err)?);
+            continue;
+        }
+
+        let (addr_field, rest) = split_field(line);
+        let addr = parse_addr(addr_field).ok_or_else(|| err("invalid address"))?;
+
+        if section == Section::Comments {
+            let comment = rest.to_string();
+            match symbols.iter_mut().find(|s| s.addr == addr) {
+                Some(symbol) => symbol.comment = Some(comment),
+                None => symbols.push(Symbol {
+                    addr,
+                    name: String::new(),
+                    comment: Some(comment),
+                }),
+            }
+            continue;
+        }
+
+        let (name, comment) = split_field(rest);
+        if name.is_empty() {
+            return Err(err("missing symbol name"));
+        }
+        let comment = comment.trim_start_matches(';').trim();
+        symbols.push(Symbol {
+            addr,
+            name: name.to_string(),
+            comment: (!comment.is_empty()).then(|| comment.to_string()),
+        });
+    }
+
+    Ok(symbols)
+}
+
+/// Adds named symbols to `labels`, replacing any existing name, and their
+/// comments to `comments` as label line comments.
+pub fn apply(symbols: &[Symbol], labels: &mut Labels, comments: &mut Comments) {
+    for symbol in symbols {
+        if !symbol.name.is_empty() {
+            labels.insert(symbol.addr, symbol.name.clone());
+        }
+        if let Some(comment) = &symbol.comment {
+            comments.set_label(symbol.addr, comment.clone());
+        }
+    }
+}
+
+fn split_field(s: &str) -> (&str, &str) {
+    match s.split_once(char::is_whitespace) {
+        Some((field, rest)) => (field, rest.trim_start()),
+        None => (s, ""),
+    }
+}
+
+/// Accepts `ADDR`, `$ADDR`, `0xADDR` and WLA-DX `BANK:ADDR`.
+fn parse_addr(field: &str) -> Option<u16> {
+    let field = match field.split_once(':') {
+        Some((bank, addr)) if u8::from_str_radix(bank, 16).is_ok() => addr,
+        Some(_) => return None,
+        None => field,
+    };
+    crate::parse_addr(field).ok()
+}
+
+/// True for Mesen labels of other memory types, such as
+/// `PrgRom:8000:reset`, which `.mlb` files for a whole SNES game include.
+fn is_other_mesen(line: &str) -> bool {
+    let Some((memory, _)) = line.split_once(':') else {
+        return false;
+    };
+    !memory.starts_with("Spc")
+        && memory.chars().all(|c| c.is_ascii_alphabetic())
+        && !memory.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn parse_mesen(line: &str) -> Option<Result<Symbol, &'static str>> {
+    let mut fields = line.splitn(4, ':');
+    let memory = fields.next()?;
+    if !memory.starts_with("Spc") {
+        return None;
+    }
+    let addr = fields.next().and_then(|f| f.split('-').next());
+    let Some(mut addr) = addr.and_then(|f| crate::parse_addr(f).ok()) else {
+        return Some(Err("invalid address"));
+    };
+    if memory == "SpcRom" {
+        if addr >= IPL_ROM.len() as u16 {
+            return Some(Err("invalid address"));
+        }
+        addr += IPL_ADDR;
+    }
+    let name = fields.next().unwrap_or("").trim();
+    let comment = fields.next().unwrap_or("").trim();
+    if name.is_empty() && comment.is_empty() {
+        return Some(Err("missing symbol name"));
+    }
+    Some(Ok(Symbol {
+        addr,
+        name: name.to_string(),
+        comment: (!comment.is_empty()).then(|| comment.to_string()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(addr: u16, name: &str, comment: Option<&str>) -> Symbol {
+        Symbol {
+            addr,
+            name: name.to_string(),
+            comment: comment.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parses_plain_lines() {
+        let text = "; driver symbols\n0800 play_note ; entry point\n\n0a40 cmd_table\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            [
+                symbol(0x0800, "play_note", Some("entry point")),
+                symbol(0x0a40, "cmd_table", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_sym_sections() {
+        let text = "[labels]\n00:0800 play_note\n00:0900 stop_note\n\n\
+                    [comments]\n00:0800 entry point\n00:0a00 no label here\n\n\
+                    [source files]\n0000 driver.asm\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            [
+                symbol(0x0800, "play_note", Some("entry point")),
+                symbol(0x0900, "stop_note", None),
+                symbol(0x0a00, "", Some("no label here")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_mesen_labels() {
+        let text = "SpcRam:0800:play_note:entry point\nSpcRam:00f4-00f7:ports\n\
+                    SpcRam:0a00::comment only\nPrgRom:8000:reset\n\
+                    SpcRom:003e:reset_vector\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            [
+                symbol(0x0800, "play_note", Some("entry point")),
+                symbol(0x00f4, "ports", None),
+                symbol(0x0a00, "", Some("comment only")),
+                symbol(0xfffe, "reset_vector", None),
+            ]
+        );
+        assert!(parse("SpcRom:0040:past_the_rom\n").is_err());
+    }
+
+    #[test]
+    fn accepts_dollar_and_0x_addresses() {
+        assert_eq!(
+            parse("$0800 a\n0x0900 b\n").unwrap(),
+            [symbol(0x0800, "a", None), symbol(0x0900, "b", None)]
+        );
+    }
+
+    #[test]
+    fn reports_the_malformed_line() {
+        let err = parse("0800 ok\nzz00 bad\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.starts_with("invalid address"));
+
+        let err = parse("0800\n").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(err.message.starts_with("missing symbol name"));
+
+        let err = parse("SpcRam:xyz:name\n").unwrap_err();
+        assert!(err.message.starts_with("invalid address"));
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Assembler syntax the listing is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dialect {
+    /// byuu's spcdas: `mov a,$1234+x`, `set3 $12`, `mov1 c,$1234,5`.
+    #[default]
+    Spcdas,
+    /// bass with `arch snes.smp`, which shares spcdas's operand syntax.
+    Bass,
+    /// WLA-DX (wla-spc700): `!` for absolute, `set1 $12.3`, `.db`/`.dw`.
+    #[serde(alias = "wla-dx")]
+    Wla,
+    /// asar with `arch spc700`: `set1 $12.3`, `mov1 c,$1234.5`.
+    Asar,
+    /// ca65 with SPC700 macros: `a:` forces absolute, `.byte`/`.word`.
+    Ca65,
+    /// Sony's manual notation in lower case: `mov a,!$1234`,
+    /// `mov1 c,/$1234.5`.
+    Sony,
+    /// The notation of Sony's SPC700 manual: `MOV A, [dp]+Y`,
+    /// `MOVW YA, dp`, `JMP [!a+X]`, `MOV1 C, /mem.bit`.
+    Official,
+}
+
+/// How direct-page bit instructions name their bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitStyle {
+    /// The bit is part of the mnemonic: `set3 $12`, `bbs3 $12,target`.
+    Mnemonic,
+    /// `set1 $12.3`, `bbs $12.3,target`.
+    Dotted,
+}
+
+/// The notation a dialect uses where the SPC700 assemblers disagree.
+#[derive(Debug)]
+pub struct Syntax {
+    /// Written before absolute operands so they are not taken for direct
+    /// page ones.
+    pub abs_prefix: &'static str,
+    pub bit_style: BitStyle,
+    /// Between the address and bit of a `mem.bit` operand.
+    pub mem_bit_separator: char,
+    /// Whether a negated `mem.bit` is written `/mem.bit` rather than
+    /// `!(mem,bit)`.
+    pub slash_not: bool,
+    pub byte_directive: &'static str,
+    pub word_directive: &'static str,
+    /// Line put at the top of a listing to select the CPU.
+    pub header: Option<&'static str>,
+    /// Upper-case mnemonics and register names.
+    pub uppercase: bool,
+    /// Written between operands.
+    pub operand_separator: &'static str,
+    /// Whether memory indirection is written with brackets, `[dp+x]` and
+    /// `[dp]+y`, rather than parentheses.
+    pub brackets: bool,
+    /// Whether `mov dp,dp` style operands are written without the
+    /// parentheses spcdas puts around them.
+    pub bare_dp_to_dp: bool,
+}
+
+static SPCDAS: Syntax = Syntax {
+    abs_prefix: "",
+    bit_style: BitStyle::Mnemonic,
+    mem_bit_separator: ',',
+    slash_not: false,
+    byte_directive: "db",
+    word_directive: "dw",
+    header: None,
+    uppercase: false,
+    operand_separator: ",",
+    brackets: false,
+    bare_dp_to_dp: false,
+};
+
+static BASS: Syntax = Syntax {
+    header: Some("arch snes.smp"),
+    ..SPCDAS
+};
+
+static WLA: Syntax = Syntax {
+    byte_directive: ".db",
+    word_directive: ".dw",
+    ..SONY
+};
+
+static ASAR: Syntax = Syntax {
+    abs_prefix: "",
+    header: Some("arch spc700"),
+    ..SONY
+};
+
+static CA65: Syntax = Syntax {
+    abs_prefix: "a:",
+    byte_directive: ".byte",
+    word_directive: ".word",
+    ..SPCDAS
+};
+
+static SONY: Syntax = Syntax {
+    abs_prefix: "!",
+    bit_style: BitStyle::Dotted,
+    mem_bit_separator: '.',
+    slash_not: true,
+    ..SPCDAS
+};
+
+static OFFICIAL: Syntax = Syntax {
+    byte_directive: "DB",
+    word_directive: "DW",
+    uppercase: true,
+    operand_separator: ", ",
+    brackets: true,
+    bare_dp_to_dp: true,
+    ..SONY
+};
+
+impl Dialect {
+    pub fn syntax(self) -> &'static Syntax {
+        match self {
+            Dialect::Spcdas => &SPCDAS,
+            Dialect::Bass => &BASS,
+            Dialect::Wla => &WLA,
+            Dialect::Asar => &ASAR,
+            Dialect::Ca65 => &CA65,
+            Dialect::Sony => &SONY,
+            Dialect::Official => &OFFICIAL,
+        }
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "spcdas" => Ok(Dialect::Spcdas),
+            "bass" => Ok(Dialect::Bass),
+            "wla" | "wla-dx" | "wladx" => Ok(Dialect::Wla),
+            "asar" => Ok(Dialect::Asar),
+            "ca65" => Ok(Dialect::Ca65),
+            "sony" => Ok(Dialect::Sony),
+            "official" => Ok(Dialect::Official),
+            _ => Err(format!(
+                "unknown syntax '{}' (expected spcdas, bass, wla, asar, ca65, sony or official)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Dialect::Spcdas => "spcdas",
+            Dialect::Bass => "bass",
+            Dialect::Wla => "wla",
+            Dialect::Asar => "asar",
+            Dialect::Ca65 => "ca65",
+            Dialect::Sony => "sony",
+            Dialect::Official => "official",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,159 @@
+use crate::cpu::{Cpu, flags};
+use crate::formatter;
+use crate::spc::Spc;
+use std::io::{self, Write};
+
+/// Column the register state starts at, wide enough for the address, hex
+/// bytes and disassembly of every instruction.
+const REGISTER_COLUMN: usize = 40;
+
+const FLAG_NAMES: [(u8, char); 8] = [
+    (flags::N, 'n'),
+    (flags::V, 'v'),
+    (flags::P, 'p'),
+    (flags::B, 'b'),
+    (flags::H, 'h'),
+    (flags::I, 'i'),
+    (flags::Z, 'z'),
+    (flags::C, 'c'),
+];
+
+/// When a trace stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceLimit {
+    Instructions(u64),
+    Cycles(u64),
+}
+
+/// Runs `cpu` and writes one line per instruction: the instruction as the
+/// listing formats it, then the registers, flags and cycle count before it
+/// executes in the layout of bsnes trace logs, then the listing's comments.
+/// Stops at `limit` or when the CPU halts.
+pub fn write_trace(
+    writer: &mut dyn Write,
+    spc: &mut Spc,
+    cpu: &mut Cpu,
+    limit: TraceLimit,
+) -> io::Result<()> {
+    let mut steps = 0;
+    while !cpu.halted {
+        let done = match limit {
+            TraceLimit::Instructions(n) => steps >= n,
+            TraceLimit::Cycles(n) => cpu.cycles >= n,
+        };
+        if done {
+            break;
+        }
+
+        let instr = match cpu.next_instruction(spc) {
+            Ok(instr) => instr,
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        spc.direct_page = cpu.direct_page();
+        let regs = cpu.regs;
+        spc.dsp_tracker
+            .set_known(regs.a, regs.x, regs.y, spc.mem[0x00F2]);
+        let mut line = format!(
+            "{:<width$} {}",
+            formatter::format_code(&instr, spc),
+            format_state(cpu),
+            width = REGISTER_COLUMN
+        );
+        let comments = formatter::format_comments(&instr, spc);
+        if !comments.is_empty() {
+            line.push_str(" ; ");
+            line.push_str(&comments.join("; "));
+        }
+        writeln!(writer, "{}", line)?;
+
+        cpu.step(spc)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        steps += 1;
+    }
+    Ok(())
+}
+
+/// `A:00 X:00 Y:00 SP:ef YA:0000 nvPbhiZc CYC:0`, with set flags in
+/// uppercase.
+pub fn format_state(cpu: &Cpu) -> String {
+    let regs = &cpu.regs;
+    let flags: String = FLAG_NAMES
+        .iter()
+        .map(|&(flag, name)| {
+            if cpu.flag(flag) {
+                name.to_ascii_uppercase()
+            } else {
+                name
+            }
+        })
+        .collect();
+    format!(
+        "A:{:02x} X:{:02x} Y:{:02x} SP:{:02x} YA:{:04x} {} CYC:{}",
+        regs.a,
+        regs.x,
+        regs.y,
+        regs.sp,
+        cpu.ya(),
+        flags,
+        cpu.cycles
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spc::Config;
+
+    fn trace(code: &[u8], limit: TraceLimit) -> Vec<String> {
+        let config = Config {
+            show_hex: false,
+            ..Config::default()
+        };
+        let mut spc = Spc::new(config, 0x0200, 0x0200);
+        spc.load_rom(code, 0x0200);
+        let mut cpu = Cpu::new(&spc);
+        let mut text = Vec::new();
+        write_trace(&mut text, &mut spc, &mut cpu, limit).unwrap();
+        String::from_utf8(text)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    const PROGRAM: [u8; 7] = [
+        0xE8, 0x80, // mov a,#$80
+        0xCD, 0x00, // mov x,#$00
+        0x00, // nop
+        0xEF, // sleep
+        0x00, // nop
+    ];
+
+    #[test]
+    fn writes_registers_flags_and_cycles_until_sleep() {
+        assert_eq!(
+            trace(&PROGRAM, TraceLimit::Instructions(100)),
+            [
+                "0200: mov   a,#$80                       A:00 X:00 Y:00 SP:00 YA:0000 nvpbhizc CYC:0",
+                "0202: mov   x,#$00                       A:80 X:00 Y:00 SP:00 YA:0080 Nvpbhizc CYC:2",
+                "0204: nop                                A:80 X:00 Y:00 SP:00 YA:0080 nvpbhiZc CYC:4",
+                "0205: sleep                              A:80 X:00 Y:00 SP:00 YA:0080 nvpbhiZc CYC:6",
+            ]
+        );
+        // stop halts the same way.
+        assert_eq!(trace(&[0xFF, 0x00], TraceLimit::Instructions(100)).len(), 1);
+    }
+
+    #[test]
+    fn stops_at_the_instruction_or_cycle_limit() {
+        let lines = trace(&PROGRAM, TraceLimit::Instructions(2));
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("0202:"));
+
+        // The instruction that reaches the limit still runs.
+        let lines = trace(&PROGRAM, TraceLimit::Cycles(3));
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].ends_with("CYC:2"));
+        assert!(trace(&PROGRAM, TraceLimit::Cycles(0)).is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+use crate::data::DataKind;
+use crate::flow;
+use crate::hwregs;
+use crate::instruction::{DecodedInstruction, DecodedOperand, FlowKind};
+use crate::listing::Item;
+use crate::spc::Spc;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+
+/// `mov` opcodes whose memory operand is the destination.
+const MOV_STORES: [u8; 12] = [
+    0x8F, 0xC4, 0xC5, 0xC9, 0xCB, 0xCC, 0xD4, 0xD5, 0xD6, 0xD8, 0xD9, 0xDB,
+];
+
+/// How an instruction or pointer refers to an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XrefKind {
+    Read,
+    Write,
+    /// Read and written back, as by `inc`, `set1` or `dbnz`.
+    Modify,
+    /// Branched or jumped to.
+    Jump,
+    Call,
+    /// An entry of a pointer table.
+    Pointer,
+}
+
+impl fmt::Display for XrefKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag = match self {
+            XrefKind::Read => "r",
+            XrefKind::Write => "w",
+            XrefKind::Modify => "rw",
+            XrefKind::Jump => "j",
+            XrefKind::Call => "c",
+            XrefKind::Pointer => "p",
+        };
+        f.write_str(tag)
+    }
+}
+
+/// One reference to an address, from the instruction or pointer at `from`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Xref {
+    pub from: u16,
+    pub kind: XrefKind,
+}
+
+impl fmt::Display for Xref {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "${:04x}({})", self.from, self.kind)
+    }
+}
+
+/// Every reference made by the listed code, by the address referred to.
+#[derive(Debug, Default)]
+pub struct Xrefs {
+    refs: BTreeMap<u16, Vec<Xref>>,
+}
+
+impl Xrefs {
+    /// References to `addr`, in the order of the referring addresses.
+    pub fn get(&self, addr: u16) -> &[Xref] {
+        self.refs.get(&addr).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn add(&mut self, addr: u16, xref: Xref) {
+        self.refs.entry(addr).or_default().push(xref);
+    }
+
+    /// The `xref: $0812(c), $0930(w)` comment for `addr`, if anything
+    /// refers to it.
+    pub fn comment(&self, addr: u16) -> Option<String> {
+        let refs = self.get(addr);
+        (!refs.is_empty()).then(|| format!("xref: {}", join(refs)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &[Xref])> {
+        self.refs
+            .iter()
+            .map(|(&addr, refs)| (addr, refs.as_slice()))
+    }
+}
+
+fn join(refs: &[Xref]) -> String {
+    refs.iter()
+        .map(Xref::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Collects the references made by `items`. Direct page operands are
+/// resolved with the P flag followed from `spc.direct_page` through the
+/// listed `setp`/`clrp`, the same way the listing shows them.
+pub fn collect(items: &[Item], spc: &Spc) -> Xrefs {
+    let mut xrefs = Xrefs::default();
+    let mut direct_page = spc.direct_page;
+    for item in items {
+        match *item {
+            Item::Instruction(ref instr) => {
+                for (addr, kind) in references(instr, direct_page, spc) {
+                    xrefs.add(
+                        addr,
+                        Xref {
+                            from: instr.address,
+                            kind,
+                        },
+                    );
+                }
+                match instr.definition.opcode {
+                    0x20 => direct_page = 0x0000,
+                    0x40 => direct_page = 0x0100,
+                    _ => {}
+                }
+            }
+            Item::Data {
+                addr,
+                len,
+                kind: DataKind::Pointers,
+            } => {
+                for i in (0..len).step_by(2) {
+                    let from = addr.wrapping_add(i);
+                    xrefs.add(
+                        spc.read_word(from),
+                        Xref {
+                            from,
+                            kind: XrefKind::Pointer,
+                        },
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    for refs in xrefs.refs.values_mut() {
+        refs.sort_by_key(|xref| xref.from);
+        refs.dedup();
+    }
+    xrefs
+}
+
+/// The addresses `instr` refers to and how. Indexed operands refer to
+/// their base address, word operations to their low byte, and indirect
+/// ones to the pointer they read.
+pub fn references(instr: &DecodedInstruction, direct_page: u16, spc: &Spc) -> Vec<(u16, XrefKind)> {
+    let mnemonic = instr.definition.mnemonic;
+    let opcode = instr.definition.opcode;
+    let dp = |addr: u8| direct_page | addr as u16;
+
+    let mut refs = match instr.operand {
+        DecodedOperand::DpToDp { dest, src } => {
+            let dest_kind = match mnemonic {
+                "mov" => XrefKind::Write,
+                "cmp" => XrefKind::Read,
+                _ => XrefKind::Modify,
+            };
+            vec![(dp(dest), dest_kind), (dp(src), XrefKind::Read)]
+        }
+        DecodedOperand::IndirectX(addr) | DecodedOperand::IndirectY(addr) => {
+            vec![(dp(addr), XrefKind::Read)]
+        }
+        DecodedOperand::DpImm { addr, .. } => {
+            let kind = match mnemonic {
+                "mov" => XrefKind::Write,
+                "cmp" => XrefKind::Read,
+                _ => XrefKind::Modify,
+            };
+            vec![(dp(addr), kind)]
+        }
+        _ => {
+            let kind = data_access(mnemonic, opcode);
+            instr
+                .operand_addresses(direct_page)
+                .into_iter()
+                .map(|addr| (addr, kind))
+                .collect()
+        }
+    };
+
+    let kind = match instr.flow() {
+        FlowKind::Call => XrefKind::Call,
+        _ => XrefKind::Jump,
+    };
+    if let Some(target) = instr
+        .branch_target()
+        .or_else(|| flow::call_target(instr, spc))
+    {
+        refs.push((target, kind));
+    }
+    refs
+}
+
+/// How a single memory operand of `mnemonic` is accessed.
+fn data_access(mnemonic: &str, opcode: u8) -> XrefKind {
+    match mnemonic {
+        "mov" if MOV_STORES.contains(&opcode) => XrefKind::Write,
+        // movw dp,ya
+        "movw" if opcode == 0xDA => XrefKind::Write,
+        // mov1 mem.bit,c
+        "mov1" if opcode == 0xCA => XrefKind::Modify,
+        "inc" | "dec" | "asl" | "lsr" | "rol" | "ror" | "incw" | "decw" | "tset1" | "tclr1"
+        | "not1" | "dbnz" => XrefKind::Modify,
+        // set0-set7 and clr0-clr7
+        _ if opcode & 0x0F == 0x02 => XrefKind::Modify,
+        _ => XrefKind::Read,
+    }
+}
+
+/// Writes every referenced address with its name and references, one per
+/// line. I/O registers are named as the listing names them, with
+/// `hw_regs`:
+///
+/// ```text
+/// 00f2  DSPADDR          $0804(w), $0810(w)
+/// 0820  sub_0820         $080c(c)
+/// ```
+pub fn write_report(writer: &mut dyn Write, spc: &Spc) -> io::Result<()> {
+    for (addr, refs) in spc.xrefs.iter() {
+        let name = spc
+            .labels
+            .get(addr)
+            .or_else(|| {
+                spc.config
+                    .hw_regs
+                    .then(|| hwregs::lookup(addr))
+                    .flatten()
+                    .map(|reg| reg.name)
+            })
+            .unwrap_or("");
+        writeln!(writer, "{:04x}  {:<16} {}", addr, name, join(refs))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DataKind;
+    use crate::decoder::decode_one;
+    use crate::spc::Config;
+
+    fn refs(code: &[u8], direct_page: u16) -> Vec<(u16, XrefKind)> {
+        let mut spc = Spc::new(Config::default(), 0x0800, 0x0800);
+        spc.load_rom(code, 0x0800);
+        references(&decode_one(&spc, 0x0800).unwrap(), direct_page, &spc)
+    }
+
+    #[test]
+    fn references_name_how_memory_is_used() {
+        use XrefKind::*;
+        // mov a,$1234
+        assert_eq!(refs(&[0xE5, 0x34, 0x12], 0), [(0x1234, Read)]);
+        // mov $12,a with P set
+        assert_eq!(refs(&[0xC4, 0x12], 0x0100), [(0x0112, Write)]);
+        // inc $12
+        assert_eq!(refs(&[0xAB, 0x12], 0), [(0x0012, Modify)]);
+        // mov $34,$12 and cmp $34,$12
+        assert_eq!(
+            refs(&[0xFA, 0x12, 0x34], 0),
+            [(0x0034, Write), (0x0012, Read)]
+        );
+        assert_eq!(
+            refs(&[0x69, 0x12, 0x34], 0),
+            [(0x0034, Read), (0x0012, Read)]
+        );
+        // dbnz $12,$0800 modifies its counter and jumps
+        assert_eq!(
+            refs(&[0x6E, 0x12, 0xFD], 0),
+            [(0x0012, Modify), (0x0800, Jump)]
+        );
+        // bra $0800
+        assert_eq!(refs(&[0x2F, 0xFE], 0), [(0x0800, Jump)]);
+        // call $0a00
+        assert_eq!(refs(&[0x3F, 0x00, 0x0A], 0), [(0x0A00, Call)]);
+    }
+
+    #[test]
+    fn data_access_follows_the_opcode() {
+        assert_eq!(data_access("mov", 0xC5), XrefKind::Write);
+        assert_eq!(data_access("mov", 0xE5), XrefKind::Read);
+        assert_eq!(data_access("movw", 0xDA), XrefKind::Write);
+        assert_eq!(data_access("movw", 0xBA), XrefKind::Read);
+        assert_eq!(data_access("mov1", 0xCA), XrefKind::Modify);
+        assert_eq!(data_access("mov1", 0xAA), XrefKind::Read);
+        assert_eq!(data_access("set1", 0x62), XrefKind::Modify);
+        assert_eq!(data_access("clr1", 0xF2), XrefKind::Modify);
+        assert_eq!(data_access("tset1", 0x0E), XrefKind::Modify);
+    }
+
+    #[test]
+    fn pointer_tables_refer_to_their_entries() {
+        let mut spc = Spc::new(Config::default(), 0x0800, 0x0804);
+        spc.load_rom(&[0x00, 0x09, 0x40, 0x09], 0x0800);
+        let items = [Item::Data {
+            addr: 0x0800,
+            len: 4,
+            kind: DataKind::Pointers,
+        }];
+        let xrefs = collect(&items, &spc);
+        assert_eq!(
+            xrefs.get(0x0900),
+            [Xref {
+                from: 0x0800,
+                kind: XrefKind::Pointer
+            }]
+        );
+        assert_eq!(xrefs.comment(0x0940).unwrap(), "xref: $0802(p)");
+    }
+
+    #[test]
+    fn report_names_io_registers_only_with_hw_regs() {
+        for hw_regs in [false, true] {
+            let config = Config {
+                hw_regs,
+                ..Config::default()
+            };
+            let mut spc = Spc::new(config, 0x0800, 0x0800);
+            spc.xrefs.add(
+                0x00F2,
+                Xref {
+                    from: 0x0800,
+                    kind: XrefKind::Write,
+                },
+            );
+            let mut report = Vec::new();
+            write_report(&mut report, &spc).unwrap();
+            let name = if hw_regs { "DSPADDR" } else { "" };
+            assert_eq!(
+                String::from_utf8(report).unwrap(),
+                format!("00f2  {:<16} $0800(w)\n", name)
+            );
+        }
+    }
+}